@@ -1,25 +1,57 @@
 use indicatif::MultiProgress;
 use lopdf::{Bookmark, Document, Object, ObjectId};
-use pdfuse_utils::{error_t, get_progress_indicator, log, Indexed};
+use pdfuse_sizing::CustomSize;
+use pdfuse_utils::{error_t, get_progress_indicator, warn_t, Indexed};
 use rayon::prelude::*;
+use serde::Serialize;
 use size_guide::SizeGuide;
-use std::{collections::BTreeMap, fmt::Display, path::PathBuf};
+use std::{collections::{BTreeMap, HashMap, HashSet}, fmt::Display, path::{Path, PathBuf}, sync::Arc, time::Instant};
 
 pub use imager::Imager;
 pub use loaded_document::LoadedDocument;
+use loaded_document::media_box_size;
 pub use loaded_image::LoadedImage;
 use pdfuse_parameters::{
-    source_path::display_path,
-    Parameters,
+    ErrorPolicy, ImagePageSizing, Parameters,
     SourcePath::{self, Image, LibreDocument, Pdf},
 };
+use jpeg2000::Jpeg2000Data;
+use text_page::TextPage;
 
-use crate::DocumentLoadError;
+use crate::{
+    error::{ImageLoadError, MergeError},
+    report::{Phase, PrunedObjects, ReportBuilder},
+    DocumentLoadError,
+};
+mod bilevel;
+mod blank_pages;
+mod caption;
+mod cmyk;
+mod collate;
+mod color_profile;
+mod exif;
+mod grid;
+mod helvetica;
+mod image_filters;
 mod imager;
+mod jpeg;
+mod jpeg2000;
 mod loaded_document;
 mod loaded_image;
+mod mirror_margins;
 mod optional_thread;
+mod page_tree;
+mod placeholder;
+mod resolution;
+mod save;
+mod scan_cleanup;
+mod separator;
 mod size_guide;
+#[cfg(test)]
+mod test_documents;
+mod text_page;
+mod tiff_pages;
+mod tone_map;
 use optional_thread::OptionalThread;
 
 /// Applies `f` to each element of `iter` and collects the results into a `Vec`
@@ -32,15 +64,30 @@ where
 }
 
 pub enum Data {
-    Image(LoadedImage),
+    /// Frames of an image, each becoming a page.
+    Image(Vec<LoadedImage>),
+    /// A JPEG 2000 image, which is embedded without being decoded.
+    Jpeg2000(Jpeg2000Data),
     Document(LoadedDocument),
 }
 
 pub type PdfResult<T> = std::result::Result<T, DocumentLoadError>;
 pub type IndexedPdfResult<T> = Indexed<PdfResult<T>>;
 
-impl From<LoadedImage> for Data {
-    fn from(value: LoadedImage) -> Self {
+/// Summary of a merge run, used to pick the exit code of the application.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MergeOutcome {
+    /// Every input is in the output file.
+    Complete,
+    /// The output file was written, but `failed` inputs are missing from it or were replaced with placeholders.
+    Partial { failed: usize },
+    /// No output file was written, or none of the inputs made it into it.
+    Failed,
+}
+
+impl From<Vec<LoadedImage>> for Data {
+    fn from(value: Vec<LoadedImage>) -> Self {
         Self::Image(value)
     }
 }
@@ -54,9 +101,20 @@ impl From<LoadedDocument> for Data {
 impl Display for Data {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Data::Image(image_data) => write!(
+            Data::Image(frames) => match frames.first() {
+                Some(image_data) => write!(
+                    f,
+                    "Image data: {w}x{h}, {count} frame(s), from \"{path}\"",
+                    w = image_data.width(),
+                    h = image_data.height(),
+                    count = frames.len(),
+                    path = image_data.source_path().display()
+                ),
+                None => write!(f, "Image data: no frames"),
+            },
+            Data::Jpeg2000(image_data) => write!(
                 f,
-                "Image data: {w}x{h}, from \"{path}\"",
+                "JPEG 2000 data: {w}x{h}, from \"{path}\"",
                 w = image_data.width(),
                 h = image_data.height(),
                 path = image_data.source_path().display()
@@ -94,6 +152,7 @@ fn size_information_not_needed(
     parameters: &Parameters,
     optional_thread: OptionalThread,
     multi_progress: &MultiProgress,
+    report: &ReportBuilder,
 ) -> Vec<IndexedPdfResult<Document>> {
     run_in_parallel_with_libre(
         loaded_images,
@@ -101,6 +160,7 @@ fn size_information_not_needed(
         parameters,
         optional_thread,
         multi_progress,
+        report,
     )
 }
 /// Images (if any) do not need to rely on libre documents' sizes.
@@ -112,6 +172,7 @@ fn run_in_parallel_with_libre(
     parameters: &Parameters,
     optional_thread: OptionalThread,
     multi_progress: &MultiProgress,
+    report: &ReportBuilder,
 ) -> Vec<IndexedPdfResult<Document>> {
     let loaded_images_pdfs: Vec<IndexedPdfResult<Data>> =
         loaded_images.into_iter().chain(loaded_pdfs).collect();
@@ -119,12 +180,12 @@ fn run_in_parallel_with_libre(
     let guide = SizeGuide::new(&loaded_images_pdfs, parameters);
 
     let images_and_pdfs: Vec<Indexed<Result<Document, DocumentLoadError>>> =
-        parallel_documentize(parameters, &guide, loaded_images_pdfs, multi_progress);
+        parallel_documentize(parameters, &guide, loaded_images_pdfs, multi_progress, report);
 
     // join the parallel thread now, after converting all images
     let converted_documents = optional_thread.get_converted_data();
     let loaded_documents =
-        parallel_documentize(parameters, &guide, converted_documents, multi_progress);
+        parallel_documentize(parameters, &guide, converted_documents, multi_progress, report);
 
     let mut all_items: Vec<IndexedPdfResult<Document>> = images_and_pdfs
         .into_iter()
@@ -143,6 +204,7 @@ fn wait_for_libre(
     parameters: &Parameters,
     optional_thread: OptionalThread,
     multi_progress: &MultiProgress,
+    report: &ReportBuilder,
 ) -> Vec<IndexedPdfResult<Document>> {
     let loaded_converted_documents: Vec<IndexedPdfResult<Data>> =
         optional_thread.get_converted_data();
@@ -155,7 +217,8 @@ fn wait_for_libre(
 
     let guide = SizeGuide::new(&loaded_all, parameters);
 
-    let mut all_items = parallel_documentize(parameters, &guide, loaded_all, multi_progress);
+    let mut all_items =
+        parallel_documentize(parameters, &guide, loaded_all, multi_progress, report);
     all_items.sort_by_key(|x| x.index());
     all_items
 }
@@ -165,62 +228,205 @@ fn parallel_documentize(
     guide: &SizeGuide,
     loaded_all: Vec<Indexed<Result<Data, DocumentLoadError>>>,
     multi_progress: &MultiProgress,
+    report: &ReportBuilder,
 ) -> Vec<Indexed<Result<Document, DocumentLoadError>>> {
     let bar = multi_progress.add(get_progress_indicator(
         loaded_all.len() as u64,
         "Converting images and PDFs",
     ));
+    // documents converted by LibreOffice come in a later call, without images
+    let has_images = loaded_all
+        .iter()
+        .any(|loaded| matches!(loaded.value(), Ok(Data::Image(_) | Data::Jpeg2000(_))));
+    let caption_font = has_images.then(|| caption::read_font(parameters)).flatten();
     loaded_all
         .into_par_iter()
         .map(|loaded| {
             let index = loaded.index();
-            let value = match loaded.unwrap() {
-                Ok(data) => match data {
-                    Data::Image(loaded_image) => {
-                        let mut imager = Imager::new(
-                            "title",
-                            guide.get_size(index),
-                            parameters.image_dpi,
-                            parameters.margin,
-                            parameters.image_quality,
-                            parameters.image_lossless_compression,
-                        );
-                        let path = display_path(loaded_image.source_path());
-                        match imager.add_image(loaded_image) {
-                            Ok(_) => (),
-                            Err(e) => log::error!("{e} - {path}"),
-                        }
-                        Ok(imager.close_and_into_document())
-                    }
-                    Data::Document(loaded_document) => Ok(loaded_document.into()),
-                },
-                Err(err) => Err(err),
+            let start = Instant::now();
+            let documents = match loaded.unwrap() {
+                Ok(Data::Image(frames)) => {
+                    let imager =
+                        image_imager(parameters, guide.get_size(index), caption_font.as_deref());
+                    frames_into_documents(index, frames, imager, parameters)
+                }
+                Ok(Data::Jpeg2000(image)) => {
+                    let mut imager =
+                        image_imager(parameters, guide.get_size(index), caption_font.as_deref());
+                    let (placement, alignment) = parameters.placement_for(image.source_path());
+                    imager.add_jpeg2000(image, placement, alignment);
+                    vec![Indexed::new(index, Ok(imager.close_and_into_document()))]
+                }
+                Ok(Data::Document(loaded_document)) => {
+                    vec![Indexed::new(index, Ok(loaded_document.into()))]
+                }
+                Err(err) => vec![Indexed::new(index, Err(err))],
             };
-            Indexed::new(index, value)
+            report.record_conversion(index, start.elapsed());
+            documents
         })
         .inspect(|_| bar.inc(1))
+        .flatten()
         .collect()
 }
 
-pub fn load(sources: Vec<Indexed<SourcePath>>, parameters: &Parameters) {
+/// Lays out the frames of the image at `index`, and of the inputs joined into it for a grid, in one document.
+///
+/// Frames that fail are left out, and every input they come from gets an error of its own, so that a broken input
+/// neither takes the others sharing its pages down with it nor goes unreported.
+fn frames_into_documents(
+    index: usize,
+    frames: Vec<LoadedImage>,
+    mut imager: Imager,
+    parameters: &Parameters,
+) -> Vec<IndexedPdfResult<Document>> {
+    let cleaned: Vec<Result<LoadedImage, (usize, ImageLoadError)>> = frames
+        .into_par_iter()
+        .map(|mut frame| {
+            let input_index = frame.input_index();
+            let mut clean = || {
+                scan_cleanup::clean_up(
+                    &mut frame,
+                    parameters.deskew,
+                    parameters.auto_crop_tolerance,
+                )?;
+                let filters = parameters.filters_for(frame.source_path());
+                image_filters::apply(&mut frame, filters)
+            };
+            match clean() {
+                Ok(()) => Ok(frame),
+                Err(err) => Err((input_index, err)),
+            }
+        })
+        .collect();
+    let (mut images, mut failed) = (vec![], vec![]);
+    for frame in cleaned {
+        match frame {
+            Ok(frame) => images.push(frame),
+            Err(failure) => failed.push(failure),
+        }
+    }
+    let mut inputs: Vec<Option<usize>> = images
+        .iter()
+        .map(|image| Some(image.input_index()))
+        .collect();
+    let errors = imager.add_images(images, |path| parameters.placement_for(path));
+    for (position, err) in errors {
+        failed.extend(
+            inputs[position]
+                .take()
+                .map(|input_index| (input_index, err)),
+        );
+    }
+    let mut documents = vec![];
+    // the pages go with the first input that is on them, which is `index` unless it failed
+    match inputs.into_iter().flatten().min() {
+        Some(first) => documents.push(Indexed::new(first, Ok(imager.close_and_into_document()))),
+        None if failed.is_empty() => {
+            documents.push(Indexed::new(index, Ok(imager.close_and_into_document())))
+        }
+        None => {}
+    }
+    // an input is reported once, with the first of its frames that failed
+    let mut reported = HashSet::new();
+    for (input_index, err) in failed {
+        if reported.insert(input_index) {
+            documents.push(Indexed::new(input_index, Err(err.into())));
+        }
+    }
+    documents
+}
+
+/// Creates an imager laying out images on pages of `page_size` as the parameters say.
+fn image_imager(
+    parameters: &Parameters,
+    page_size: CustomSize,
+    caption_font: Option<&[u8]>,
+) -> Imager {
+    let imager = Imager::new(
+        "title",
+        page_size,
+        parameters.image_dpi,
+        parameters.margin,
+        parameters.image_quality,
+        parameters.image_lossless_compression,
+    )
+    .with_auto_orient(parameters.auto_orient)
+    .with_mirrored_margins(parameters.mirror_margins)
+    .with_grid(parameters.grid, parameters.grid_gutter)
+    .with_caption(
+        parameters.caption.clone(),
+        parameters.caption_position,
+        parameters.caption_font_size,
+        caption_font,
+    )
+    .with_image_dpi(parameters.use_image_dpi)
+    .with_bilevel_compression(parameters.bilevel_compression, parameters.bilevel_threshold);
+    match parameters.image_page_sizing {
+        ImagePageSizing::Fallback => imager,
+        ImagePageSizing::MatchImage => imager.with_page_matching_image(
+            parameters.image_page_min_size,
+            parameters.image_page_max_size,
+        ),
+    }
+}
+
+pub fn load(sources: Vec<Indexed<SourcePath>>, parameters: &Parameters) -> MergeOutcome {
     if !sources.is_sorted_by_key(|x| x.index()) {
         panic!("Paths are supposed to be sorted already!");
     }
+    let report = Arc::new(ReportBuilder::new(&sources));
+    let outcome = load_and_merge(sources, parameters, &report);
+    if let Some(report_file) = &parameters.report_file {
+        if let Err(err) = report.build(&parameters.output_file, outcome).save(report_file) {
+            error_t!("error.report_saving", path = report_file, error = err);
+        }
+    }
+    outcome
+}
+
+fn load_and_merge(
+    sources: Vec<Indexed<SourcePath>>,
+    parameters: &Parameters,
+    report: &Arc<ReportBuilder>,
+) -> MergeOutcome {
+    let source_list = sources.clone();
     let parent_bar = MultiProgress::new();
     // let busy = BusyIndicator::new_with_message("Loading files...");
     let branch = SizeGuide::need_to_wait_for_pdf_threads(&sources, parameters);
     let SplitPathsResult(images_to_load, pdfs_to_load, documents_to_pdf) = split_paths(sources);
 
-    let conversion_thread =
-        OptionalThread::create(documents_to_pdf, parameters, parent_bar.clone());
+    let conversion_thread = OptionalThread::create(
+        documents_to_pdf,
+        parameters,
+        parent_bar.clone(),
+        report.clone(),
+    );
+    let loading_start = Instant::now();
     // load all PDFs as Data - limited only by disk IO
-    let loaded_pdfs = vector_map(pdfs_to_load, preload_pdf_indexed);
+    let loaded_pdfs = vector_map(pdfs_to_load, |p| preload_pdf_indexed(p, report));
 
     // load all images as Data - limited only by disk IO
-    let loaded_images: Vec<IndexedPdfResult<Data>> =
-        vector_map(images_to_load, preload_image_indexed);
+    let mut loaded_images: Vec<IndexedPdfResult<Data>> =
+        vector_map(images_to_load, |p| preload_image_indexed(p, parameters, report));
+    report.record_phase(Phase::Loading, loading_start.elapsed());
+
+    let blank_images = match parameters.remove_blank_pages {
+        true => blank_pages::find_blank_images(&mut loaded_images, parameters),
+        false => HashSet::new(),
+    };
+    // images sharing grid pages lose their blank frames before they are laid out
+    let (loaded_images, blank_images, joined_inputs) = match parameters.grid {
+        Some(_) => {
+            let (images, joined_inputs) =
+                grid::join_consecutive_images(loaded_images, &blank_images, &source_list, report);
+            (images, HashSet::new(), joined_inputs)
+        }
+        None => (loaded_images, blank_images, HashMap::new()),
+    };
 
     // drop(busy);
+    let conversion_start = Instant::now();
     let all_documents_to_merge = match branch {
         size_guide::GuideRequirement::SizeInformationNotNeeded => size_information_not_needed(
             loaded_images,
@@ -228,6 +434,7 @@ pub fn load(sources: Vec<Indexed<SourcePath>>, parameters: &Parameters) {
             parameters,
             conversion_thread,
             &parent_bar,
+            report,
         ),
         size_guide::GuideRequirement::WaitForLibreConversion => wait_for_libre(
             loaded_images,
@@ -235,6 +442,7 @@ pub fn load(sources: Vec<Indexed<SourcePath>>, parameters: &Parameters) {
             parameters,
             conversion_thread,
             &parent_bar,
+            report,
         ),
         size_guide::GuideRequirement::RunInParallelWithLibreConversion => {
             run_in_parallel_with_libre(
@@ -243,28 +451,242 @@ pub fn load(sources: Vec<Indexed<SourcePath>>, parameters: &Parameters) {
                 parameters,
                 conversion_thread,
                 &parent_bar,
+                report,
             )
         }
     };
-    merge_documents(
-        all_documents_to_merge.into_iter().map(|x| x.unwrap()),
+    report.record_phase(Phase::Conversion, conversion_start.elapsed());
+    let (documents, failed) =
+        match apply_error_policy(all_documents_to_merge, &source_list, parameters, report) {
+            Ok(result) => result,
+            Err(err) => {
+                error_t!("error.merge_failed", error = err);
+                return MergeOutcome::Failed;
+            }
+        };
+    if documents.is_empty() {
+        error_t!("error.merge_failed", error = MergeError::NothingToMerge);
+        return MergeOutcome::Failed;
+    }
+    // blank pages are found before collating, but removed after, so that fronts and backs stay paired
+    let blank_pages = match parameters.remove_blank_pages {
+        true => blank_pages::find_blank_pages(&documents, &blank_images, &source_list, report),
+        false => HashSet::new(),
+    };
+    let (documents, separator_pages) = match parameters.collate {
+        Some(order) => match collate::collate(documents, order, parameters.collate_fronts) {
+            Ok(documents) => (documents, false),
+            Err(err) => {
+                error_t!("error.merge_failed", error = err);
+                return MergeOutcome::Failed;
+            }
+        },
+        None => (documents, parameters.separator_pages),
+    };
+    let documents = blank_pages::remove_blank_pages(documents, &blank_pages);
+    if documents.is_empty() {
+        error_t!("error.merge_failed", error = MergeError::NothingToMerge);
+        return MergeOutcome::Failed;
+    }
+    record_output_pages(&documents, &joined_inputs, &failed, separator_pages, report);
+    let mut documents = match separator_pages {
+        true => add_separators(documents, &source_list, parameters),
+        false => documents,
+    };
+    // the sides of the sheets are only known once all pages are in place
+    if parameters.mirror_margins {
+        mirror_margins::mirror(&mut documents);
+    }
+    let merging_start = Instant::now();
+    let merge_result = merge_documents(
+        documents.into_iter().map(Indexed::unwrap),
         &parameters.output_file,
+        parameters,
     );
+    report.record_phase(Phase::Merging, merging_start.elapsed());
+    match merge_result {
+        Ok(pruned) => report.record_pruned(pruned),
+        Err(err) => {
+            error_t!("error.merge_failed", error = err);
+            return MergeOutcome::Failed;
+        }
+    }
+    // a file made only of placeholders is still a failure
+    match (source_list.len() - failed.len(), failed.len()) {
+        (0, _) => MergeOutcome::Failed,
+        (_, 0) => MergeOutcome::Complete,
+        (_, failed) => MergeOutcome::Partial { failed },
+    }
+}
+
+/// Records which pages of the output come from which input.
+///
+/// With `separator_pages` every input is preceded by an extra page, which is not counted as its own.
+/// Inputs in `joined_inputs` share the pages of the document they were joined into, unless they are among the
+/// `failed` ones, which have documents of their own if any. When the first input of a group failed, its pages go
+/// with the first one that did not.
+fn record_output_pages(
+    documents: &[Indexed<Document>],
+    joined_inputs: &HashMap<usize, Vec<usize>>,
+    failed: &HashSet<usize>,
+    separator_pages: bool,
+    report: &ReportBuilder,
+) {
+    let leaders: HashMap<usize, usize> = joined_inputs
+        .iter()
+        .flat_map(|(lead, joined)| joined.iter().map(move |index| (*index, *lead)))
+        .collect();
+    let mut next_page = 1;
+    for document in documents {
+        if separator_pages {
+            next_page += 1;
+        }
+        let pages = document.get_pages();
+        let page_size = pages
+            .values()
+            .next()
+            .and_then(|page| media_box_size(document, *page));
+        let index = document.index();
+        // the placeholder of a failed input is its own, the pages of a group are shared by all that did not fail
+        let group = match failed.contains(&index) {
+            true => None,
+            false => leaders.get(&index).or(Some(&index)),
+        };
+        let joined = group
+            .into_iter()
+            .flat_map(|lead| {
+                std::iter::once(lead).chain(joined_inputs.get(lead).into_iter().flatten())
+            })
+            .filter(|joined| **joined != index && !failed.contains(joined));
+        for index in std::iter::once(&index).chain(joined) {
+            report.record_output(*index, next_page, pages.len(), page_size);
+        }
+        next_page += pages.len();
+    }
+}
+
+/// Inserts a separator page before each document.
+fn add_separators(
+    documents: Vec<Indexed<Document>>,
+    sources: &[Indexed<SourcePath>],
+    parameters: &Parameters,
+) -> Vec<Indexed<Document>> {
+    let separators: Vec<Document> = documents
+        .par_iter()
+        .map(|document| {
+            let source = source_at(sources, document.index());
+            separator::separator_document(source, document.value(), parameters)
+        })
+        .collect();
+    separators
+        .into_iter()
+        .zip(documents)
+        .flat_map(|(separator, document)| {
+            [Indexed::new(document.index(), separator), document]
+        })
+        .collect()
+}
+
+/// Deals with inputs that failed to load, according to [`Parameters::on_error`].
+///
+/// Returns the documents to merge and the indexes of the inputs that failed to load.
+fn apply_error_policy(
+    items: Vec<IndexedPdfResult<Document>>,
+    sources: &[Indexed<SourcePath>],
+    parameters: &Parameters,
+    report: &ReportBuilder,
+) -> Result<(Vec<Indexed<Document>>, HashSet<usize>), MergeError> {
+    let mut documents = Vec::with_capacity(items.len());
+    let mut failed = HashSet::new();
+    for item in items {
+        let index = item.index();
+        let err = match item.unwrap() {
+            Ok(document) => {
+                documents.push(Indexed::new(index, document));
+                continue;
+            }
+            Err(err) => err,
+        };
+        failed.insert(index);
+        let source = source_at(sources, index);
+        error_t!("error.source_loading", path = source, error = err);
+        report.record_error(index, &err);
+        match parameters.on_error {
+            ErrorPolicy::Skip => (),
+            ErrorPolicy::Abort => return Err(MergeError::Aborted),
+            ErrorPolicy::Placeholder => documents.push(Indexed::new(
+                index,
+                placeholder_document(source, &err, parameters),
+            )),
+        }
+    }
+    Ok((documents, failed))
+}
+
+/// Returns the source with the given index. `sources` have to be sorted by index.
+fn source_at(sources: &[Indexed<SourcePath>], index: usize) -> &SourcePath {
+    let position = sources
+        .binary_search_by_key(&index, |x| x.index())
+        .expect("Every loaded item should come from a source path");
+    sources[position].value()
+}
+
+/// Creates a single page saying which file failed to load, and why.
+fn placeholder_document(
+    source: &SourcePath,
+    err: &DocumentLoadError,
+    parameters: &Parameters,
+) -> Document {
+    let mut page = TextPage::new(parameters.image_page_fallback_size);
+    page.add_line(rust_i18n::t!("placeholder.title"))
+        .add_line(source.to_string())
+        .add_line("")
+        .add_line(err.to_string());
+    page.into_document()
 }
 
-fn preload_image_indexed(path: Indexed<PathBuf>) -> Indexed<PdfResult<Data>> {
-    path.map_with_index(|path| LoadedImage::load(&path).map(Into::into).map_err(Into::into))
+fn preload_image_indexed(
+    path: Indexed<PathBuf>,
+    parameters: &Parameters,
+    report: &ReportBuilder,
+) -> Indexed<PdfResult<Data>> {
+    let start = Instant::now();
+    let index = path.index();
+    let loaded = path.map_with_index(|path| match jpeg2000::read_if_signed(&path)? {
+        Some(bytes) => load_jpeg2000(bytes, &path, parameters),
+        None => LoadedImage::load_frames(&path, index, parameters).map(Into::into),
+    });
+    report.record_load(index, start.elapsed());
+    loaded
 }
-fn preload_pdf_indexed(path: Indexed<PathBuf>) -> Indexed<PdfResult<Data>> {
-    path.map_with_index(preload_pdf)
+/// Reads a JPEG 2000 file to be embedded as it is, as the `image` crate cannot decode it.
+fn load_jpeg2000(bytes: Vec<u8>, path: &Path, parameters: &Parameters) -> PdfResult<Data> {
+    let image = Jpeg2000Data::parse(bytes, path).ok_or(ImageLoadError::InvalidJpeg2000)?;
+    // it is not one of the frames joined into grid pages, see [`grid::join_consecutive_images`]
+    if parameters.grid.is_some() {
+        warn_t!("warning.jpeg2000_grid", path = path.display());
+    }
+    Ok(Data::Jpeg2000(image))
+}
+
+fn preload_pdf_indexed(path: Indexed<PathBuf>, report: &ReportBuilder) -> Indexed<PdfResult<Data>> {
+    let start = Instant::now();
+    let index = path.index();
+    let loaded = path.map_with_index(preload_pdf);
+    report.record_load(index, start.elapsed());
+    loaded
 }
 fn preload_pdf(path: PathBuf) -> PdfResult<Data> {
     LoadedDocument::load_pdf(&path).map(LoadedDocument::into)
 }
 
-pub fn merge_documents<T>(documents: T, output_path: &str)
+pub fn merge_documents<T>(
+    documents: T,
+    output_path: &str,
+    parameters: &Parameters,
+) -> Result<PrunedObjects, MergeError>
 where
-    T: IntoIterator<Item = PdfResult<Document>>,
+    T: IntoIterator<Item = Document>,
 {
     // Define a starting max_id (will be used as start index for object_ids)
     let mut max_id = 1;
@@ -273,18 +695,18 @@ where
     let mut documents_pages: BTreeMap<ObjectId, Object> = BTreeMap::new();
     let mut documents_objects: BTreeMap<ObjectId, Object> = BTreeMap::new();
     let mut document = Document::with_version("1.5");
+    // objects shared by the pages of a collated input, by input and old id
+    let mut shared_objects = HashMap::new();
     // https://github.com/J-F-Liu/lopdf/blob/0d65f6ed5b55fde1a583861535b4bfc6cdf42de1/README.md
-    for result in documents {
-        if result.is_err() {
-            error_t!("error.image_loading", path = result.unwrap_err());
-            continue;
-        }
-        let mut doc = result.unwrap();
+    for mut doc in documents {
         let mut first = false;
 
-        doc.renumber_objects_with(max_id);
-        max_id = doc.max_id + 1;
+        max_id = page_tree::renumber_objects(&mut doc, max_id, &mut shared_objects);
 
+        // pages are moved to a different tree, so they cannot rely on their old ancestors anymore
+        for page_id in doc.get_pages().into_values() {
+            page_tree::materialize_inherited_attributes(&mut doc, page_id);
+        }
         documents_pages.extend(
             doc.get_pages()
                 .into_values()
@@ -305,7 +727,7 @@ where
 
     // Catalog and Pages are mandatory
     let mut catalog_object: Option<(ObjectId, Object)> = None;
-    let mut pages_object: Option<(ObjectId, Object)> = None;
+    let mut pages_object: Option<ObjectId> = None;
 
     // Process all objects except "Page" type
     for (object_id, object) in documents_objects.iter() {
@@ -324,25 +746,9 @@ where
                 ));
             }
             b"Pages" => {
-                // Collect and update a first "Pages" object and use it for the future "Catalog"
-                // We have also to merge all dictionaries of the old and the new "Pages" object
-                if let Ok(dictionary) = object.as_dict() {
-                    let mut dictionary = dictionary.clone();
-                    if let Some((_, ref object)) = pages_object {
-                        if let Ok(old_dictionary) = object.as_dict() {
-                            dictionary.extend(old_dictionary);
-                        }
-                    }
-
-                    pages_object = Some((
-                        if let Some((id, _)) = pages_object {
-                            id
-                        } else {
-                            *object_id
-                        },
-                        Object::Dictionary(dictionary),
-                    ));
-                }
+                // Reuse the id of the first "Pages" object for the root of the new page tree.
+                // Attributes of the old nodes are already copied into the pages.
+                pages_object.get_or_insert(*object_id);
             }
             b"Page" => {}     // Ignored, processed later and separately
             b"Outlines" => {} // Ignored, not supported yet
@@ -354,56 +760,28 @@ where
     }
 
     // If no "Pages" object found abort
-    if pages_object.is_none() {
-        error_t!("debug.root_not_found", item = "Page");
-        return;
-    }
-    // Iterate over all "Page" objects and collect into the parent "Pages" created before
+    let Some(pages_id) = pages_object else {
+        return Err(MergeError::RootNotFound("Page"));
+    };
+    // Insert all "Page" objects, they get their parents when building the page tree
     for (object_id, object) in documents_pages.iter() {
-        if let Ok(dictionary) = object.as_dict() {
-            let mut dictionary = dictionary.clone();
-            dictionary.set("Parent", pages_object.as_ref().unwrap().0);
-
-            document
-                .objects
-                .insert(*object_id, Object::Dictionary(dictionary));
-        }
+        document.objects.insert(*object_id, object.clone());
     }
 
     // If no "Catalog" found abort
-    if catalog_object.is_none() {
-        error_t!("debug.root_not_found", item = "Catalog");
-        return;
-    }
-
-    let catalog_object = catalog_object.unwrap();
-    let pages_object = pages_object.unwrap();
-
-    // Build a new "Pages" with updated fields
-    if let Ok(dictionary) = pages_object.1.as_dict() {
-        let mut dictionary = dictionary.clone();
-
-        // Set new pages count
-        dictionary.set("Count", documents_pages.len() as u32);
-
-        // Set new "Kids" list (collected from documents pages) for "Pages"
-        dictionary.set(
-            "Kids",
-            documents_pages
-                .into_keys()
-                .map(Object::Reference)
-                .collect::<Vec<_>>(),
-        );
+    let Some(catalog_object) = catalog_object else {
+        return Err(MergeError::RootNotFound("Catalog"));
+    };
 
-        document
-            .objects
-            .insert(pages_object.0, Object::Dictionary(dictionary));
-    }
+    // Build a new, balanced page tree under the reused "Pages" id
+    document.max_id = max_id;
+    let page_ids: Vec<ObjectId> = documents_pages.into_keys().collect();
+    page_tree::build_page_tree(&mut document, pages_id, &page_ids);
 
     // Build a new "Catalog" with updated fields
     if let Ok(dictionary) = catalog_object.1.as_dict() {
         let mut dictionary = dictionary.clone();
-        dictionary.set("Pages", pages_object.0);
+        dictionary.set("Pages", pages_id);
         dictionary.remove(b"Outlines"); // Outlines not supported in merged PDFs
 
         document
@@ -431,8 +809,10 @@ where
 
     document.compress();
 
-    document.save(output_path).unwrap();
+    let pruned = save::prune_unreferenced(&mut document);
+    save::save_document(&mut document, output_path, parameters)?;
     // Save the merged PDF
     // Store file in current working directory.
     // Note: Line is excluded when running tests
+    Ok(pruned)
 }
@@ -0,0 +1,233 @@
+use fax::{encoder::Encoder, Color, VecWriter};
+use image::{DynamicImage, GrayImage, Luma};
+use lopdf::{dictionary, Stream};
+
+/// Difference between the color channels of a pixel above which it is counted as colored.
+const COLOR_SPREAD: u8 = 48;
+/// Part of the pixels which may be colored in an image that is still turned black and white.
+const MAX_COLORED: f64 = 0.05;
+/// Gray levels counted as neither dark nor light.
+const MIDTONES: std::ops::RangeInclusive<u8> = 64..=191;
+/// Part of the pixels which may be midtones in an image that is still turned black and white.
+const MAX_MIDTONES: f64 = 0.25;
+
+/// Image XObject with one bit per pixel, if the image is gray with only black and white pixels.
+///
+/// Scanners save such images with 1 bit per pixel, which decoders widen to 8.
+pub(crate) fn to_stream(image: &DynamicImage) -> Option<Stream> {
+    let DynamicImage::ImageLuma8(gray) = image else {
+        return None;
+    };
+    let packed = pack(gray)?;
+    let dictionary = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => gray.width() as i64,
+        "Height" => gray.height() as i64,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 1,
+    };
+    let mut stream = Stream::new(dictionary, packed);
+    let _ = stream.compress();
+    Some(stream)
+}
+
+/// Image XObject compressed with CCITT Group 4, as used by fax machines and document scanners.
+///
+/// Pixels darker than the threshold turn black; without a threshold it is chosen by [`otsu_threshold`].
+pub(crate) fn to_fax_stream(image: &DynamicImage, threshold: Option<u8>) -> Stream {
+    let gray = image.to_luma8();
+    let threshold = threshold.unwrap_or_else(|| otsu_threshold(&gray));
+    let black_and_white = GrayImage::from_fn(gray.width(), gray.height(), |x, y| {
+        Luma([if gray.get_pixel(x, y).0[0] < threshold {
+            0
+        } else {
+            255
+        }])
+    });
+    let Ok(width) = u16::try_from(black_and_white.width()) else {
+        // wider than Group 4 allows
+        return to_stream(&DynamicImage::ImageLuma8(black_and_white))
+            .expect("thresholded image has only black and white pixels");
+    };
+    let mut encoder = Encoder::new(VecWriter::new());
+    for row in black_and_white.rows() {
+        let colors = row.map(|pixel| match pixel.0[0] {
+            0 => Color::Black,
+            _ => Color::White,
+        });
+        // writing to a vector cannot fail
+        let _ = encoder.encode_line(colors, width);
+    }
+    let encoded = match encoder.finish() {
+        Ok(writer) => writer.finish(),
+        Err(never) => match never {},
+    };
+    let dictionary = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => black_and_white.width() as i64,
+        "Height" => black_and_white.height() as i64,
+        "ColorSpace" => "DeviceGray",
+        "BitsPerComponent" => 1,
+        "Filter" => "CCITTFaxDecode",
+        "DecodeParms" => dictionary! {
+            "K" => -1,
+            "Columns" => black_and_white.width() as i64,
+            "Rows" => black_and_white.height() as i64,
+        },
+    };
+    Stream::new(dictionary, encoded).with_compression(false)
+}
+
+/// Whether the image looks like a scanned document, which loses nothing but noise when turned black and white.
+///
+/// Such images are nearly gray and most of their pixels are already close to black or white,
+/// unlike photos, drawings in color and gray scans of pictures.
+pub(crate) fn looks_bilevel(image: &DynamicImage) -> bool {
+    let rgb = image.to_rgb8();
+    let total = rgb.len() as f64 / 3.0;
+    let (mut colored, mut midtones) = (0u64, 0u64);
+    for pixel in rgb.pixels() {
+        let [red, green, blue] = pixel.0;
+        if red.max(green).max(blue) - red.min(green).min(blue) > COLOR_SPREAD {
+            colored += 1;
+        }
+        let gray = (red as u32 * 299 + green as u32 * 587 + blue as u32 * 114) / 1000;
+        if MIDTONES.contains(&(gray as u8)) {
+            midtones += 1;
+        }
+    }
+    colored as f64 <= total * MAX_COLORED && midtones as f64 <= total * MAX_MIDTONES
+}
+
+/// Gray level separating dark and light pixels best, by Otsu's method.
+pub(crate) fn otsu_threshold(image: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total: u64 = histogram.iter().sum();
+    let total_sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(level, &count)| level as f64 * count as f64)
+        .sum();
+    let (mut dark_count, mut dark_sum) = (0u64, 0.0);
+    let (mut best, mut best_variance) = (128, 0.0);
+    // pixels below `threshold` are dark
+    for threshold in 1..=255 {
+        dark_count += histogram[threshold - 1];
+        dark_sum += (threshold - 1) as f64 * histogram[threshold - 1] as f64;
+        let light_count = total - dark_count;
+        if dark_count == 0 || light_count == 0 {
+            continue;
+        }
+        let dark_mean = dark_sum / dark_count as f64;
+        let light_mean = (total_sum - dark_sum) / light_count as f64;
+        let variance = dark_count as f64 * light_count as f64 * (dark_mean - light_mean).powi(2);
+        if variance > best_variance {
+            (best, best_variance) = (threshold as u8, variance);
+        }
+    }
+    best
+}
+
+/// Packs the pixels eight to a byte, with every row starting on a new byte and 1 for white.
+///
+/// Returns `None` if any pixel is neither black nor white.
+fn pack(image: &GrayImage) -> Option<Vec<u8>> {
+    let row_length = (image.width() as usize).div_ceil(8);
+    let mut packed = Vec::with_capacity(row_length * image.height() as usize);
+    for row in image.rows() {
+        let mut byte = 0u8;
+        let mut filled = 0;
+        for pixel in row {
+            let bit = match pixel.0[0] {
+                0 => 0,
+                255 => 1,
+                _ => return None,
+            };
+            byte = (byte << 1) | bit;
+            filled += 1;
+            if filled == 8 {
+                packed.push(byte);
+                (byte, filled) = (0, 0);
+            }
+        }
+        if filled > 0 {
+            packed.push(byte << (8 - filled));
+        }
+    }
+    Some(packed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_rows() {
+        // a black column on the left, a white one on the right
+        let image = GrayImage::from_fn(10, 2, |x, _| Luma([if x == 0 { 0 } else { 255 }]));
+        assert_eq!(pack(&image).unwrap(), [0x7F, 0xC0, 0x7F, 0xC0]);
+        let stream = to_stream(&DynamicImage::ImageLuma8(image)).unwrap();
+        assert_eq!(
+            stream
+                .dict
+                .get(b"BitsPerComponent")
+                .unwrap()
+                .as_i64()
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn keeps_gray_images() {
+        let image = GrayImage::from_pixel(3, 3, Luma([128]));
+        assert!(to_stream(&DynamicImage::ImageLuma8(image)).is_none());
+        assert!(to_stream(&DynamicImage::new_rgb8(2, 2)).is_none());
+    }
+
+    #[test]
+    fn tells_scans_from_pictures() {
+        let text = GrayImage::from_fn(20, 20, |x, y| {
+            Luma([if (x + y) % 5 == 0 { 20 } else { 235 }])
+        });
+        assert!(looks_bilevel(&text.into()));
+        let gradient = GrayImage::from_fn(256, 1, |x, _| Luma([x as u8]));
+        assert!(!looks_bilevel(&gradient.into()));
+        let red = image::RgbImage::from_pixel(4, 4, image::Rgb([250, 30, 30]));
+        assert!(!looks_bilevel(&red.into()));
+    }
+
+    #[test]
+    fn picks_threshold_between_levels() {
+        let image = GrayImage::from_fn(8, 8, |x, _| Luma([if x < 3 { 40 } else { 210 }]));
+        let threshold = otsu_threshold(&image);
+        assert!((41..=210).contains(&threshold));
+    }
+
+    #[test]
+    fn encodes_group_4() {
+        let image =
+            GrayImage::from_fn(20, 6, |x, y| Luma([if (x + y) % 7 < 3 { 30 } else { 220 }]));
+        let stream = to_fax_stream(&DynamicImage::ImageLuma8(image.clone()), None);
+        assert_eq!(
+            stream.dict.get(b"Filter").unwrap().as_name().unwrap(),
+            b"CCITTFaxDecode"
+        );
+        let mut rows = vec![];
+        fax::decoder::decode_g4(stream.content.iter().copied(), 20, Some(6), |transitions| {
+            rows.push(fax::decoder::pels(transitions, 20).collect::<Vec<_>>());
+        })
+        .unwrap();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, color) in row.iter().enumerate() {
+                let dark = image.get_pixel(x as u32, y as u32).0[0] < 128;
+                assert_eq!(*color == Color::Black, dark);
+            }
+        }
+    }
+}
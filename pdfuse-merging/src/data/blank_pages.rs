@@ -0,0 +1,191 @@
+use std::collections::HashSet;
+
+use lopdf::{content::Operation, Document, ObjectId};
+use pdfuse_parameters::{Parameters, SourcePath};
+use pdfuse_utils::{info_t, Indexed};
+use rayon::prelude::*;
+
+use super::{source_at, Data, IndexedPdfResult};
+use crate::report::ReportBuilder;
+
+/// Operators which paint something on the page, apart from text.
+const PAINTING_OPERATORS: [&str; 12] = [
+    "S", "s", "f", "F", "f*", "B", "B*", "b", "b*", "sh", "Do", "BI",
+];
+const TEXT_OPERATORS: [&str; 4] = ["Tj", "TJ", "'", "\""];
+
+/// Returns input indices and page numbers (counted from 1) of the frames of loaded images
+/// with less ink than [`Parameters::blank_threshold`].
+///
+/// The frames are decoded to count their ink; those which cannot be decoded are kept, and fail when converted.
+pub(crate) fn find_blank_images(
+    loaded_images: &mut [IndexedPdfResult<Data>],
+    parameters: &Parameters,
+) -> HashSet<(usize, u32)> {
+    loaded_images
+        .par_iter_mut()
+        .flat_map_iter(|item| {
+            let index = item.index();
+            let frames = match item.value_mut() {
+                Ok(Data::Image(frames)) => frames.as_mut_slice(),
+                _ => &mut [],
+            };
+            frames
+                .iter_mut()
+                .zip(1..)
+                .filter_map(|(frame, page_number)| {
+                    let ink = frame.ink_coverage().ok()?;
+                    (ink < parameters.blank_threshold).then_some((index, page_number))
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Finds blank pages in the documents: pages made from `blank_images` and PDF pages that draw nothing.
+///
+/// Removed pages are logged and recorded in the report, numbered within their input.
+/// The pages are identified by input index and object id, which survive splitting the documents into pages.
+pub(crate) fn find_blank_pages(
+    documents: &[Indexed<Document>],
+    blank_images: &HashSet<(usize, u32)>,
+    sources: &[Indexed<SourcePath>],
+    report: &ReportBuilder,
+) -> HashSet<(usize, ObjectId)> {
+    documents
+        .par_iter()
+        .flat_map_iter(|document| {
+            let index = document.index();
+            let blank: Vec<(u32, ObjectId)> = document
+                .value()
+                .get_pages()
+                .into_iter()
+                .filter(|(page_number, page_id)| {
+                    blank_images.contains(&(index, *page_number))
+                        || is_blank(document.value(), *page_id)
+                })
+                .collect();
+            if !blank.is_empty() {
+                let source = source_at(sources, index);
+                for (page_number, _) in &blank {
+                    info_t!("info.blank_page_removed", path = source, page = page_number);
+                }
+                report.record_removed_pages(index, blank.iter().map(|(number, _)| *number as usize));
+            }
+            blank.into_iter().map(move |(_, page_id)| (index, page_id))
+        })
+        .collect()
+}
+
+/// Deletes the pages found by [`find_blank_pages`], dropping documents that are left without pages.
+pub(crate) fn remove_blank_pages(
+    documents: Vec<Indexed<Document>>,
+    blank_pages: &HashSet<(usize, ObjectId)>,
+) -> Vec<Indexed<Document>> {
+    if blank_pages.is_empty() {
+        return documents;
+    }
+    documents
+        .into_iter()
+        .filter_map(|mut document| {
+            let index = document.index();
+            let pages = document.value().get_pages();
+            let to_delete: Vec<u32> = pages
+                .iter()
+                .filter(|(_, page_id)| blank_pages.contains(&(index, **page_id)))
+                .map(|(number, _)| *number)
+                .collect();
+            if to_delete.len() == pages.len() {
+                return None;
+            }
+            document.value_mut().delete_pages(&to_delete);
+            Some(document)
+        })
+        .collect()
+}
+
+/// Checks if the content streams of the page draw nothing visible.
+///
+/// Pages whose content cannot be read are never blank.
+fn is_blank(document: &Document, page_id: ObjectId) -> bool {
+    document
+        .get_and_decode_page_content(page_id)
+        .is_ok_and(|content| !draws_anything(&content.operations))
+}
+
+fn draws_anything(operations: &[Operation]) -> bool {
+    // text rendering modes 3 (invisible, used by OCR layers) and 7 (clipping only) draw nothing
+    let mut invisible_text = false;
+    for operation in operations {
+        let operator = operation.operator.as_str();
+        if operator == "Tr" {
+            invisible_text = operation
+                .operands
+                .first()
+                .and_then(|mode| mode.as_i64().ok())
+                .is_some_and(|mode| mode == 3 || mode == 7);
+        } else if PAINTING_OPERATORS.contains(&operator)
+            || (TEXT_OPERATORS.contains(&operator) && !invisible_text)
+        {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use lopdf::Object;
+
+    use super::*;
+
+    fn operation(operator: &str, operands: Vec<Object>) -> Operation {
+        Operation::new(operator, operands)
+    }
+
+    #[test]
+    fn empty_content_is_blank() {
+        assert!(!draws_anything(&[]));
+    }
+
+    #[test]
+    fn unpainted_path_is_blank() {
+        let operations = [
+            operation("q", vec![]),
+            operation("re", vec![0.into(), 0.into(), 10.into(), 10.into()]),
+            operation("n", vec![]),
+            operation("Q", vec![]),
+        ];
+        assert!(!draws_anything(&operations));
+    }
+
+    #[test]
+    fn filled_path_is_not_blank() {
+        let operations = [
+            operation("re", vec![0.into(), 0.into(), 10.into(), 10.into()]),
+            operation("f", vec![]),
+        ];
+        assert!(draws_anything(&operations));
+    }
+
+    #[test]
+    fn invisible_text_is_blank() {
+        let operations = [
+            operation("BT", vec![]),
+            operation("Tr", vec![3.into()]),
+            operation("Tj", vec![Object::string_literal("OCR")]),
+            operation("ET", vec![]),
+        ];
+        assert!(!draws_anything(&operations));
+    }
+
+    #[test]
+    fn text_is_not_blank() {
+        let operations = [
+            operation("BT", vec![]),
+            operation("Tj", vec![Object::string_literal("Hello")]),
+            operation("ET", vec![]),
+        ];
+        assert!(draws_anything(&operations));
+    }
+}
@@ -0,0 +1,236 @@
+use std::{fs, path::Path};
+
+use chrono::NaiveDateTime;
+use pdfuse_parameters::{CaptionPosition, Parameters};
+use pdfuse_sizing::{CustomSize, Length, Margins};
+use pdfuse_utils::warn_t;
+use printpdf::{FontId, Op, ParsedFont, PdfDocument, Point, Pt, TextItem};
+
+use super::{helvetica, separator::modification_date, LoadedImage};
+
+/// Height of the line of a caption, relative to the font size.
+const LINE_HEIGHT: f64 = 1.2;
+/// Space between the image and its caption, relative to the font size.
+const GAP: f64 = 0.5;
+/// Distance of the baseline from the bottom of the line, leaving room for descenders, relative to the font size.
+const BASELINE: f64 = 0.25;
+const ELLIPSIS: &str = "...";
+
+/// Reads the font file given for captions, if there are any.
+///
+/// Returns `None` if the file cannot be read or is not a font, which falls back to Helvetica.
+pub(crate) fn read_font(parameters: &Parameters) -> Option<Vec<u8>> {
+    parameters.caption.as_ref()?;
+    let path = parameters.caption_font.as_ref()?;
+    let path_text = path.display();
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            warn_t!("warning.caption_font", path = path_text, error = err);
+            return None;
+        }
+    };
+    if ParsedFont::from_bytes(&bytes, 0, &mut vec![]).is_none() {
+        warn_t!("warning.caption_font_invalid", path = path_text);
+        return None;
+    }
+    Some(bytes)
+}
+
+enum CaptionFont {
+    Builtin,
+    /// A font added to the document, along with its metrics.
+    Embedded(FontId, Box<ParsedFont>),
+}
+
+/// A line of text written beneath or above each image, in room taken from the drawing area.
+pub(crate) struct Caption {
+    template: String,
+    position: CaptionPosition,
+    font_size: f32,
+    font: CaptionFont,
+}
+
+impl Caption {
+    /// Creates a caption written with the font in `font_bytes`, which is added to the document, or with Helvetica.
+    pub(crate) fn new(
+        template: String,
+        position: CaptionPosition,
+        font_size: f32,
+        font_bytes: Option<&[u8]>,
+        document: &mut PdfDocument,
+    ) -> Self {
+        let font = match font_bytes.and_then(|bytes| ParsedFont::from_bytes(bytes, 0, &mut vec![]))
+        {
+            Some(font) => CaptionFont::Embedded(document.add_font(&font), Box::new(font)),
+            None => CaptionFont::Builtin,
+        };
+        Caption {
+            template,
+            position,
+            font_size,
+            font,
+        }
+    }
+
+    /// Height of the room taken for the caption, including the space between it and the image.
+    pub(crate) fn height(&self) -> Length {
+        Length::from_points(self.font_size as f64 * (LINE_HEIGHT + GAP))
+    }
+
+    /// The margins with the room for the caption added on its side.
+    pub(crate) fn reserve(&self, margin: Margins) -> Margins {
+        match self.position {
+            CaptionPosition::Below => Margins {
+                bottom: margin.bottom + self.height(),
+                ..margin
+            },
+            CaptionPosition::Above => Margins {
+                top: margin.top + self.height(),
+                ..margin
+            },
+        }
+    }
+
+    /// Fills in the template for the image.
+    pub(crate) fn text(&self, image: &LoadedImage) -> String {
+        self.text_for(image.source_path(), image.date_taken(), image.page_number())
+    }
+
+    /// Fills in the template for page `page_number` of the file, taken at `date_taken` if that is known.
+    pub(crate) fn text_for(
+        &self,
+        path: &Path,
+        date_taken: Option<NaiveDateTime>,
+        page_number: usize,
+    ) -> String {
+        let file_part = |part: Option<&std::ffi::OsStr>| {
+            part.map(|x| x.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let date = date_taken
+            .or_else(|| modification_date(path).map(|x| x.naive_local()))
+            .map(|x| x.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default();
+        self.template
+            .replace("{name}", &file_part(path.file_name()))
+            .replace("{stem}", &file_part(path.file_stem()))
+            .replace("{path}", &path.to_string_lossy())
+            .replace("{date}", &date)
+            .replace("{page}", &page_number.to_string())
+    }
+
+    /// Operations writing the text centered between the side margins, in the room reserved by [`Caption::reserve`].
+    ///
+    /// `margin` are the margins of the page without that room. Text too long for the page is shortened, characters
+    /// Helvetica cannot write are replaced.
+    pub(crate) fn ops(&self, text: &str, page_size: CustomSize, margin: Margins) -> Vec<Op> {
+        let size = self.font_size as f64;
+        let line_bottom = match self.position {
+            CaptionPosition::Below => margin.bottom.pt(),
+            CaptionPosition::Above => {
+                page_size.vertical.pt() - margin.top.pt() - size * LINE_HEIGHT
+            }
+        };
+        let available = (page_size - margin.total()).horizontal.pt();
+        let text = self.fitted(text, available);
+        let x = margin.left.pt() + (available - self.width(&text)).max(0.0) / 2.0;
+        let cursor = Op::SetTextCursor {
+            pos: Point {
+                x: Pt(x as f32),
+                y: Pt((line_bottom + size * BASELINE) as f32),
+            },
+        };
+        let mut ops = vec![Op::StartTextSection];
+        match &self.font {
+            CaptionFont::Builtin => {
+                let encoded = helvetica::encode(&text);
+                if encoded.replaced {
+                    warn_t!("warning.caption_characters", text = text);
+                }
+                ops.extend([helvetica::set_font_op(self.font_size), cursor]);
+                ops.extend(helvetica::write_ops(encoded.bytes));
+            }
+            CaptionFont::Embedded(id, _) => ops.extend([
+                Op::SetFontSize {
+                    size: Pt(self.font_size),
+                    font: id.clone(),
+                },
+                cursor,
+                Op::WriteText {
+                    items: vec![TextItem::Text(text)],
+                    font: id.clone(),
+                },
+            ]),
+        }
+        ops.push(Op::EndTextSection);
+        ops
+    }
+
+    /// Width of the text in points.
+    fn width(&self, text: &str) -> f64 {
+        let size = self.font_size as f64;
+        match &self.font {
+            CaptionFont::Builtin => helvetica::width(&helvetica::encode(text).bytes) * size,
+            CaptionFont::Embedded(_, font) => {
+                let units: f64 = text
+                    .chars()
+                    .filter_map(|c| font.lookup_glyph_index(c as u32))
+                    .map(|glyph| font.get_horizontal_advance(glyph) as f64)
+                    .sum();
+                units * size / font.font_metrics.units_per_em as f64
+            }
+        }
+    }
+
+    /// The text, shortened and ended with an ellipsis if it is wider than `available` points.
+    fn fitted(&self, text: &str, available: f64) -> String {
+        if self.width(text) <= available {
+            return text.to_owned();
+        }
+        let mut chars: Vec<char> = text.chars().collect();
+        while !chars.is_empty() {
+            chars.pop();
+            let shortened = chars.iter().collect::<String>() + ELLIPSIS;
+            if self.width(&shortened) <= available {
+                return shortened;
+            }
+        }
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn caption(position: CaptionPosition) -> Caption {
+        Caption::new(
+            "{stem} ({page})".to_owned(),
+            position,
+            10.0,
+            None,
+            &mut PdfDocument::new("test"),
+        )
+    }
+
+    #[test]
+    fn reserves_room_on_its_side() {
+        let margin = Margins::from(CustomSize::from_millimeters(20.0, 20.0));
+        let below = caption(CaptionPosition::Below).reserve(margin);
+        assert_eq!(below.top, margin.top);
+        assert!((below.bottom.pt() - margin.bottom.pt() - 17.0).abs() < 0.1);
+        let above = caption(CaptionPosition::Above).reserve(margin);
+        assert_eq!(above.bottom, margin.bottom);
+        assert!(above.top > margin.top);
+    }
+
+    #[test]
+    fn shortens_long_text() {
+        let caption = caption(CaptionPosition::Below);
+        assert_eq!(caption.fitted("short", 100.0), "short");
+        let fitted = caption.fitted("a rather long file name.jpg", 50.0);
+        assert_eq!(fitted, "a rather l...");
+        assert!(caption.width(&fitted) <= 50.0);
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::Cursor;
+
+use image::{Rgb, RgbImage, RgbaImage};
+use jpeg_encoder::{ColorType, Encoder};
+use lopdf::{dictionary, Stream};
+use zune_core::{bytestream::ZCursor, colorspace::ColorSpace, options::DecoderOptions};
+use zune_jpeg::JpegDecoder;
+
+use super::jpeg::JpegData;
+
+// CMYK images are kept with cyan, magenta, yellow and black in the four channels of an `RgbaImage`,
+// so that turning, cropping and resizing work on them like on any other image.
+
+/// Decodes the CMYK pixels of a four component JPEG, which the `image` crate would turn into RGB.
+///
+/// Adobe files store CMYK inverted, or as YCCK, and are brought back to plain CMYK.
+pub(crate) fn decode_jpeg(jpeg: &JpegData) -> Option<RgbaImage> {
+    if jpeg.components() != 4 {
+        return None;
+    }
+    let mut decoder = JpegDecoder::new(ZCursor::new(jpeg.bytes()));
+    decoder.decode_headers().ok()?;
+    let stored = decoder.input_colorspace()?;
+    if !matches!(stored, ColorSpace::CMYK | ColorSpace::YCCK) {
+        return None;
+    }
+    // asking for the stored color space returns the samples as they are
+    let options = DecoderOptions::default()
+        .set_strict_mode(false)
+        .jpeg_set_out_colorspace(stored);
+    let mut decoder = JpegDecoder::new_with_options(ZCursor::new(jpeg.bytes()), options);
+    let mut pixels = decoder.decode().ok()?;
+    for pixel in pixels.chunks_exact_mut(4) {
+        match (stored, jpeg.adobe()) {
+            (ColorSpace::YCCK, _) => {
+                let [c, m, y] = ycc_to_rgb(pixel[0], pixel[1], pixel[2]);
+                pixel.copy_from_slice(&[c, m, y, 255 - pixel[3]]);
+            }
+            (_, true) => pixel.iter_mut().for_each(|x| *x = 255 - *x),
+            (_, false) => {}
+        }
+    }
+    RgbaImage::from_raw(jpeg.width(), jpeg.height(), pixels)
+}
+
+/// Simple conversion to RGB, good enough to look for ink and to draw thumbnails.
+pub(crate) fn to_rgb(image: &RgbaImage) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [c, m, y, k] = image.get_pixel(x, y).0;
+        let channel = |v: u8| ((255 - v as u32) * (255 - k as u32) / 255) as u8;
+        Rgb([channel(c), channel(m), channel(y)])
+    })
+}
+
+/// Image XObject in `DeviceCMYK`, compressed as JPEG at the quality, or losslessly.
+pub(crate) fn to_stream(image: &RgbaImage, quality: u8, lossless: bool) -> Stream {
+    let encoded = match lossless {
+        true => None,
+        false => encode_jpeg(image, quality),
+    };
+    if let Some(jpeg) = encoded {
+        return jpeg.into_stream();
+    }
+    let dictionary = dictionary! {
+        "Type" => "XObject",
+        "Subtype" => "Image",
+        "Width" => image.width() as i64,
+        "Height" => image.height() as i64,
+        "ColorSpace" => "DeviceCMYK",
+        "BitsPerComponent" => 8,
+    };
+    let mut stream = Stream::new(dictionary, image.as_raw().clone());
+    let _ = stream.compress();
+    stream
+}
+
+/// Returns `None` for images larger than JPEG allows.
+fn encode_jpeg(image: &RgbaImage, quality: u8) -> Option<JpegData> {
+    let width = u16::try_from(image.width()).ok()?;
+    let height = u16::try_from(image.height()).ok()?;
+    let mut bytes = Cursor::new(vec![]);
+    Encoder::new(&mut bytes, quality.clamp(1, 100))
+        .encode(image.as_raw(), width, height, ColorType::Cmyk)
+        .ok()?;
+    JpegData::parse(bytes.into_inner()).ok()
+}
+
+/// YCbCr to RGB as defined by JFIF.
+fn ycc_to_rgb(y: u8, cb: u8, cr: u8) -> [u8; 3] {
+    let (y, cb, cr) = (y as f32, cb as f32 - 128.0, cr as f32 - 128.0);
+    let channel = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    [
+        channel(y + 1.402 * cr),
+        channel(y - 0.344_136 * cb - 0.714_136 * cr),
+        channel(y + 1.772 * cb),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    const COLORS: [[u8; 4]; 4] = [
+        [0, 0, 0, 0],
+        [255, 0, 0, 0],
+        [0, 0, 0, 255],
+        [20, 200, 90, 40],
+    ];
+
+    fn sample() -> RgbaImage {
+        RgbaImage::from_fn(16, 16, |x, _| Rgba(COLORS[x as usize / 4]))
+    }
+
+    fn assert_close(image: &RgbaImage) {
+        for (index, color) in COLORS.iter().enumerate() {
+            let pixel = image.get_pixel(index as u32 * 4 + 1, 8).0;
+            for (a, b) in pixel.into_iter().zip(color) {
+                assert!(a.abs_diff(*b) <= 8, "{pixel:?} instead of {color:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn reads_jpeg_cmyk_and_ycck() {
+        for color_type in [ColorType::Cmyk, ColorType::CmykAsYcck] {
+            let image = sample();
+            let mut bytes = Cursor::new(vec![]);
+            Encoder::new(&mut bytes, 100)
+                .encode(image.as_raw(), 16, 16, color_type)
+                .unwrap();
+            let jpeg = JpegData::parse(bytes.into_inner()).unwrap();
+            assert_close(&decode_jpeg(&jpeg).unwrap());
+        }
+    }
+
+    #[test]
+    fn writes_device_cmyk() {
+        let lossy = to_stream(&sample(), 90, false);
+        assert_eq!(
+            lossy.dict.get(b"Filter").unwrap().as_name().unwrap(),
+            b"DCTDecode"
+        );
+        let lossless = to_stream(&sample(), 90, true);
+        assert_eq!(
+            lossless.dict.get(b"ColorSpace").unwrap().as_name().unwrap(),
+            b"DeviceCMYK"
+        );
+        assert_eq!(
+            lossless.decompressed_content().unwrap(),
+            sample().into_raw()
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::iter;
+
+use lopdf::Document;
+use pdfuse_parameters::CollateOrder;
+use pdfuse_utils::{warn_t, Indexed};
+
+use super::page_tree::split_pages;
+use crate::error::MergeError;
+
+/// Interleaves the pages of double-sided scans: first page of the fronts, first page of the backs, and so on.
+///
+/// The fronts are the documents of the first `fronts` inputs. Without that count, the documents are split where
+/// the fronts have as many pages as the backs, or one more.
+/// Every returned document is a single page, indexed with the input it came from.
+///
+/// # Errors
+///
+/// Returns [`MergeError::CollateSplit`] if either group would be empty, or if the split point is not known.
+pub(crate) fn collate(
+    documents: Vec<Indexed<Document>>,
+    order: CollateOrder,
+    fronts: Option<usize>,
+) -> Result<Vec<Indexed<Document>>, MergeError> {
+    if documents.len() < 2 {
+        warn_t!("warning.collate_single_input");
+        return Ok(documents);
+    }
+    let split = match fronts {
+        // inputs which failed to load have no document, so the count is compared with the indices
+        Some(fronts) => documents.iter().position(|x| x.index() >= fronts),
+        None => {
+            let page_counts: Vec<usize> = documents
+                .iter()
+                .map(|x| x.value().get_pages().len())
+                .collect();
+            split_point(&page_counts)
+        }
+    };
+    let split = split
+        .filter(|split| *split > 0)
+        .ok_or(MergeError::CollateSplit)?;
+    let mut documents = documents;
+    let backs = documents.split_off(split);
+    let fronts = into_pages(documents);
+    let mut backs = into_pages(backs);
+    if order == CollateOrder::Reverse {
+        backs.reverse();
+    }
+    if fronts.len() != backs.len() {
+        warn_t!(
+            "warning.collate_uneven",
+            fronts = fronts.len(),
+            backs = backs.len()
+        );
+    }
+    let mut backs = backs.into_iter();
+    let mut collated: Vec<Indexed<Document>> = fronts
+        .into_iter()
+        .flat_map(|front| iter::once(front).chain(backs.next()))
+        .collect();
+    collated.extend(backs);
+    Ok(collated)
+}
+
+/// Returns the number of documents in the first group, so that it has as many pages as the second one, or one more.
+///
+/// Returns `None` if there is no such split, or more than one because of documents without pages.
+fn split_point(page_counts: &[usize]) -> Option<usize> {
+    let total: usize = page_counts.iter().sum();
+    let mut splits = page_counts
+        .iter()
+        .scan(0, |before, count| {
+            *before += count;
+            Some(*before)
+        })
+        .enumerate()
+        .take(page_counts.len() - 1)
+        .filter(|(_, before)| (2 * before).checked_sub(total).is_some_and(|x| x <= 1))
+        .map(|(i, _)| i + 1);
+    match (splits.next(), splits.next()) {
+        (Some(split), None) => Some(split),
+        _ => None,
+    }
+}
+
+fn into_pages(documents: Vec<Indexed<Document>>) -> Vec<Indexed<Document>> {
+    documents
+        .into_iter()
+        .flat_map(|document| {
+            let index = document.index();
+            split_pages(document.unwrap(), index)
+                .into_iter()
+                .map(move |page| Indexed::new(index, page))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_point_two_documents() {
+        assert_eq!(split_point(&[5, 5]), Some(1));
+        assert_eq!(split_point(&[5, 4]), Some(1));
+        assert_eq!(split_point(&[4, 5]), None);
+    }
+
+    #[test]
+    fn split_point_images_and_pdf() {
+        // ten front images followed by a single PDF with the backs
+        let mut counts = vec![1; 10];
+        counts.push(10);
+        assert_eq!(split_point(&counts), Some(10));
+    }
+
+    #[test]
+    fn split_point_image_sequences() {
+        assert_eq!(split_point(&[1; 6]), Some(3));
+        assert_eq!(split_point(&[1; 7]), Some(4));
+    }
+
+    #[test]
+    fn split_point_unclear() {
+        assert_eq!(split_point(&[3, 3, 4]), None);
+        // an empty document in the middle could belong to either group
+        assert_eq!(split_point(&[3, 0, 3]), None);
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, GrayAlphaImage, GrayImage, RgbImage, RgbaImage};
+use lopdf::{dictionary, Document, Object, ObjectId, Stream};
+use moxcms::{ColorProfile, DataColorSpace, Layout, TransformOptions};
+
+use super::page_tree;
+
+/// Converts the colors of the image from its embedded profile to sRGB, or to the sRGB tone curve for gray images.
+///
+/// Returns `None` if the profile cannot be read or does not describe the colors of the image.
+/// Converted images have 8 bits per component.
+pub(crate) fn to_srgb(image: &DynamicImage, profile: &[u8]) -> Option<DynamicImage> {
+    let source = ColorProfile::new_from_slice(profile).ok()?;
+    let srgb = ColorProfile::new_srgb();
+    let (width, height) = (image.width(), image.height());
+    let alpha = image.color().has_alpha();
+    let convert = |layout: Layout, target: &ColorProfile, pixels: Vec<u8>| -> Option<Vec<u8>> {
+        let transform = source
+            .create_transform_8bit(layout, target, layout, TransformOptions::default())
+            .ok()?;
+        let mut converted = vec![0; pixels.len()];
+        transform.transform(&pixels, &mut converted).ok()?;
+        Some(converted)
+    };
+    match (source.color_space, alpha) {
+        (DataColorSpace::Rgb, false) => {
+            let pixels = convert(Layout::Rgb, &srgb, image.to_rgb8().into_raw())?;
+            RgbImage::from_raw(width, height, pixels).map(DynamicImage::from)
+        }
+        (DataColorSpace::Rgb, true) => {
+            let pixels = convert(Layout::Rgba, &srgb, image.to_rgba8().into_raw())?;
+            RgbaImage::from_raw(width, height, pixels).map(DynamicImage::from)
+        }
+        (DataColorSpace::Gray, _) => {
+            let mut gray = ColorProfile::new_gray_with_gamma(2.2);
+            gray.gray_trc = srgb.red_trc.clone();
+            match alpha {
+                false => {
+                    let pixels = convert(Layout::Gray, &gray, image.to_luma8().into_raw())?;
+                    GrayImage::from_raw(width, height, pixels).map(DynamicImage::from)
+                }
+                true => {
+                    let pixels =
+                        convert(Layout::GrayAlpha, &gray, image.to_luma_alpha8().into_raw())?;
+                    GrayAlphaImage::from_raw(width, height, pixels).map(DynamicImage::from)
+                }
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Number of color components described by the profile, read from its header.
+fn components(profile: &[u8]) -> Option<i64> {
+    match profile.get(16..20)? {
+        b"GRAY" => Some(1),
+        b"RGB " => Some(3),
+        b"CMYK" => Some(4),
+        _ => None,
+    }
+}
+
+/// Color space of the image XObject when it is one of the device color spaces, with its number of components.
+fn device_color_space(document: &Document, image_id: ObjectId) -> Option<(Vec<u8>, i64)> {
+    let stream = document.get_object(image_id).and_then(Object::as_stream).ok()?;
+    let name = stream.dict.get(b"ColorSpace").and_then(Object::as_name).ok()?;
+    let components = match name {
+        b"DeviceGray" => 1,
+        b"DeviceRGB" => 3,
+        b"DeviceCMYK" => 4,
+        _ => return None,
+    };
+    Some((name.to_vec(), components))
+}
+
+/// Gives the images named with the keys of `profiles` an `/ICCBased` color space with the profile.
+///
+/// Profiles which do not match the components of the image are left out. Each distinct profile is embedded once.
+pub(crate) fn embed_profiles(document: &mut Document, mut profiles: HashMap<String, Vec<u8>>) {
+    if profiles.is_empty() {
+        return;
+    }
+    let mut embedded: HashMap<Vec<u8>, ObjectId> = HashMap::new();
+    for (name, image_id) in page_tree::page_xobjects(document) {
+        let Some(profile) = profiles.remove(&name) else {
+            continue;
+        };
+        let Some((alternate, count)) = device_color_space(document, image_id) else {
+            continue;
+        };
+        if components(&profile) != Some(count) {
+            continue;
+        }
+        let profile_id = match embedded.get(&profile) {
+            Some(id) => *id,
+            None => {
+                let dictionary = dictionary! {
+                    "N" => count,
+                    "Alternate" => Object::Name(alternate),
+                };
+                let mut stream = Stream::new(dictionary, profile.clone());
+                // a profile which does not get smaller is kept as it is
+                let _ = stream.compress();
+                let id = document.add_object(stream);
+                embedded.insert(profile, id);
+                id
+            }
+        };
+        if let Ok(image) = document
+            .get_object_mut(image_id)
+            .and_then(Object::as_stream_mut)
+        {
+            image.dict.set(
+                "ColorSpace",
+                vec![Object::Name(b"ICCBased".to_vec()), profile_id.into()],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb;
+    use moxcms::ColorProfile;
+
+    use super::*;
+    use crate::data::test_documents::document_with_pages;
+
+    #[test]
+    fn converts_to_srgb() {
+        let image = DynamicImage::from(RgbImage::from_pixel(2, 2, Rgb([200, 100, 100])));
+        let srgb = ColorProfile::new_srgb().encode().unwrap();
+        let same = to_srgb(&image, &srgb).unwrap().to_rgb8();
+        for (a, b) in same.get_pixel(0, 0).0.into_iter().zip([200, 100, 100]) {
+            assert!(a.abs_diff(b) <= 1);
+        }
+        // the same numbers mean a more saturated color in the wider Adobe RGB
+        let wide = ColorProfile::new_adobe_rgb().encode().unwrap();
+        let [red, green, blue] = to_srgb(&image, &wide).unwrap().to_rgb8().get_pixel(0, 0).0;
+        assert!(red > 210 && green <= 100 && blue <= 100);
+        assert!(to_srgb(&image, b"not a profile").is_none());
+    }
+
+    /// A document with one page drawing an image XObject in each of the color spaces, named after them.
+    fn document_with_images(color_spaces: &[&str]) -> (Document, Vec<ObjectId>) {
+        let mut document = document_with_pages(1);
+        let mut xobjects = lopdf::Dictionary::new();
+        let mut image_ids = vec![];
+        for color_space in color_spaces {
+            let image = dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => *color_space,
+                "BitsPerComponent" => 8,
+            };
+            let image_id = document.add_object(Stream::new(image, vec![0; 4]));
+            xobjects.set(*color_space, image_id);
+            image_ids.push(image_id);
+        }
+        let page_id = document.get_pages()[&1];
+        let page = document.get_dictionary_mut(page_id).unwrap();
+        page.set("Resources", dictionary! { "XObject" => xobjects });
+        (document, image_ids)
+    }
+
+    #[test]
+    fn embeds_matching_profiles() {
+        let (mut document, image_ids) = document_with_images(&["DeviceRGB", "DeviceGray"]);
+        let srgb = ColorProfile::new_srgb().encode().unwrap();
+        let profiles = HashMap::from([
+            ("DeviceRGB".to_string(), srgb.clone()),
+            ("DeviceGray".to_string(), srgb.clone()),
+        ]);
+        embed_profiles(&mut document, profiles);
+        let color_space = |id| {
+            let image = document.get_object(id).and_then(Object::as_stream).unwrap();
+            image.dict.get(b"ColorSpace").unwrap().clone()
+        };
+        let Object::Array(icc_based) = color_space(image_ids[0]) else {
+            panic!("the RGB image has no ICCBased color space");
+        };
+        assert_eq!(icc_based[0].as_name().unwrap(), b"ICCBased");
+        let mut profile = document
+            .get_object(icc_based[1].as_reference().unwrap())
+            .and_then(Object::as_stream)
+            .unwrap()
+            .clone();
+        assert_eq!(profile.dict.get(b"N").unwrap().as_i64().unwrap(), 3);
+        assert_eq!(
+            profile.dict.get(b"Alternate").unwrap().as_name().unwrap(),
+            b"DeviceRGB"
+        );
+        let _ = profile.decompress();
+        assert_eq!(profile.content, srgb);
+        // an RGB profile does not describe a gray image
+        assert_eq!(color_space(image_ids[1]).as_name().unwrap(), b"DeviceGray");
+    }
+
+    #[test]
+    fn reads_profile_components() {
+        let srgb = ColorProfile::new_srgb().encode().unwrap();
+        assert_eq!(components(&srgb), Some(3));
+        let gray = ColorProfile::new_gray_with_gamma(2.2).encode().unwrap();
+        assert_eq!(components(&gray), Some(1));
+        assert_eq!(components(b"short"), None);
+    }
+}
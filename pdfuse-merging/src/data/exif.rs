@@ -0,0 +1,167 @@
+use chrono::NaiveDateTime;
+
+/// Tag of the time the file was last changed, in the first directory.
+const DATE_TIME: u16 = 0x0132;
+/// Tag of the offset of the EXIF directory, in the first directory.
+const EXIF_DIRECTORY: u16 = 0x8769;
+/// Tag of the time the photo was taken, in the EXIF directory.
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+/// Type of entries holding text.
+const ASCII: u16 = 2;
+/// Types of entries holding offsets: LONG and IFD.
+const OFFSETS: [u16; 2] = [4, 13];
+const DATE_FORMAT: &str = "%Y:%m:%d %H:%M:%S";
+
+/// Reads the time the photo was taken from an EXIF chunk, falling back to the date and time in its first
+/// directory, which cameras set to the same time and editors to the time they saved the image.
+///
+/// The chunk is a TIFF structure, starting with its byte order mark.
+pub(crate) fn date_taken(chunk: &[u8]) -> Option<NaiveDateTime> {
+    let tiff = Tiff::new(chunk)?;
+    let first = tiff.u32(4)? as usize;
+    let original = tiff
+        .offset(first, EXIF_DIRECTORY)
+        .and_then(|exif| tiff.date(exif, DATE_TIME_ORIGINAL));
+    original.or_else(|| tiff.date(first, DATE_TIME))
+}
+
+/// A TIFF structure, whose offsets are all checked against its length.
+struct Tiff<'a> {
+    bytes: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> Tiff<'a> {
+    fn new(bytes: &'a [u8]) -> Option<Self> {
+        let big_endian = match bytes.get(0..4)? {
+            b"MM\0*" => true,
+            b"II*\0" => false,
+            _ => return None,
+        };
+        Some(Tiff { bytes, big_endian })
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self
+            .bytes
+            .get(offset..offset.checked_add(2)?)?
+            .try_into()
+            .ok()?;
+        Some(match self.big_endian {
+            true => u16::from_be_bytes(bytes),
+            false => u16::from_le_bytes(bytes),
+        })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self
+            .bytes
+            .get(offset..offset.checked_add(4)?)?
+            .try_into()
+            .ok()?;
+        Some(match self.big_endian {
+            true => u32::from_be_bytes(bytes),
+            false => u32::from_le_bytes(bytes),
+        })
+    }
+
+    /// Type, count and position of the value field of the entry with the tag in the directory at `directory`.
+    fn entry(&self, directory: usize, tag: u16) -> Option<(u16, usize, usize)> {
+        let count = self.u16(directory)?;
+        (0..count as usize)
+            .map(|index| directory + 2 + index * 12)
+            .find(|&entry| self.u16(entry) == Some(tag))
+            .and_then(|entry| {
+                let kind = self.u16(entry + 2)?;
+                let count = self.u32(entry + 4)? as usize;
+                Some((kind, count, entry + 8))
+            })
+    }
+
+    /// Offset held by the entry with the tag, like that of another directory.
+    fn offset(&self, directory: usize, tag: u16) -> Option<usize> {
+        match self.entry(directory, tag)? {
+            (kind, 1, field) if OFFSETS.contains(&kind) => Some(self.u32(field)? as usize),
+            _ => None,
+        }
+    }
+
+    /// Date and time held as text by the entry with the tag.
+    fn date(&self, directory: usize, tag: u16) -> Option<NaiveDateTime> {
+        let (ASCII, count, field) = self.entry(directory, tag)? else {
+            return None;
+        };
+        // texts of up to four bytes are held in the value field itself
+        let start = match count {
+            0..=4 => field,
+            _ => self.u32(field)? as usize,
+        };
+        let text = self.bytes.get(start..start.checked_add(count)?)?;
+        let text = std::str::from_utf8(text).ok()?;
+        NaiveDateTime::parse_from_str(text.trim_end_matches('\0'), DATE_FORMAT).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chunk with the given dates, the original one in the EXIF directory.
+    fn chunk(big_endian: bool, changed: Option<&str>, original: Option<&str>) -> Vec<u8> {
+        let short = |value: u16| match big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        let long = |value: u32| match big_endian {
+            true => value.to_be_bytes(),
+            false => value.to_le_bytes(),
+        };
+        let entry = |tag: u16, kind: u16, count: u32, value: u32| {
+            let mut bytes = short(tag).to_vec();
+            bytes.extend(short(kind));
+            bytes.extend(long(count));
+            bytes.extend(long(value));
+            bytes
+        };
+        // header, first directory with two entries at 8, EXIF directory with one entry at 38, texts at 56 and 76
+        let mut bytes = match big_endian {
+            true => b"MM\0*".to_vec(),
+            false => b"II*\0".to_vec(),
+        };
+        bytes.extend(long(8));
+        bytes.extend(short(2));
+        bytes.extend(entry(DATE_TIME, ASCII, 20, 56));
+        bytes.extend(entry(EXIF_DIRECTORY, 4, 1, 38));
+        bytes.extend(long(0));
+        bytes.extend(short(1));
+        bytes.extend(entry(DATE_TIME_ORIGINAL, ASCII, 20, 76));
+        bytes.extend(long(0));
+        for date in [changed, original] {
+            let text = format!("{:\0<20}", date.unwrap_or(""));
+            bytes.extend(text.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_dates() {
+        let (changed, original) = ("2024:05:06 07:08:09", "2023:01:02 03:04:05");
+        for big_endian in [false, true] {
+            let taken = |changed, original| {
+                date_taken(&chunk(big_endian, changed, original))
+                    .map(|x| x.format(DATE_FORMAT).to_string())
+            };
+            assert_eq!(
+                taken(Some(changed), Some(original)).as_deref(),
+                Some(original)
+            );
+            assert_eq!(taken(Some(changed), None).as_deref(), Some(changed));
+            assert_eq!(taken(None, None), None);
+        }
+        assert_eq!(date_taken(b"II*\0"), None);
+        // offsets past the end of the chunk
+        let mut truncated = chunk(true, Some(changed), Some(original));
+        truncated.truncate(70);
+        assert_eq!(date_taken(&truncated), None);
+    }
+}
@@ -0,0 +1,68 @@
+use std::collections::{HashMap, HashSet};
+
+use pdfuse_parameters::SourcePath;
+use pdfuse_utils::{info_t, Indexed};
+
+use super::{source_at, Data, IndexedPdfResult};
+use crate::report::ReportBuilder;
+
+/// Joins the frames of images given one after another into the first of them, so that they share grid pages.
+///
+/// Frames in `blank_images` are left out, and logged and recorded in the report like other removed blank pages.
+/// Images left without any frames are dropped.
+///
+/// Returns the loaded images along with the inputs joined into each of them, by input index.
+pub(crate) fn join_consecutive_images(
+    loaded_images: Vec<IndexedPdfResult<Data>>,
+    blank_images: &HashSet<(usize, u32)>,
+    sources: &[Indexed<SourcePath>],
+    report: &ReportBuilder,
+) -> (Vec<IndexedPdfResult<Data>>, HashMap<usize, Vec<usize>>) {
+    let mut images: Vec<IndexedPdfResult<Data>> = Vec::with_capacity(loaded_images.len());
+    let mut joined: HashMap<usize, Vec<usize>> = HashMap::new();
+    // position of the image taking in frames and the index of the last input joined into it
+    let mut open: Option<(usize, usize)> = None;
+    for item in loaded_images {
+        let (index, value) = item.into();
+        let frames = match value {
+            Ok(Data::Image(frames)) => frames,
+            other => {
+                images.push(Indexed::new(index, other));
+                open = None;
+                continue;
+            }
+        };
+        let blank: Vec<u32> = (1..=frames.len() as u32)
+            .filter(|page_number| blank_images.contains(&(index, *page_number)))
+            .collect();
+        if !blank.is_empty() {
+            let source = source_at(sources, index);
+            for page_number in &blank {
+                info_t!("info.blank_page_removed", path = source, page = page_number);
+            }
+            report.record_removed_pages(index, blank.iter().map(|x| *x as usize));
+        }
+        let frames = frames
+            .into_iter()
+            .zip(1..)
+            .filter(|(_, page_number)| !blank.contains(page_number))
+            .map(|(frame, _)| frame);
+        match open {
+            Some((position, last_index)) if last_index + 1 == index => {
+                let group = &mut images[position];
+                if let Ok(Data::Image(group_frames)) = group.value_mut() {
+                    group_frames.extend(frames);
+                }
+                joined.entry(group.index()).or_default().push(index);
+                open = Some((position, index));
+            }
+            _ => {
+                images.push(Indexed::new(index, Ok(Data::Image(frames.collect()))));
+                open = Some((images.len() - 1, index));
+            }
+        }
+    }
+    images.retain(|item| !matches!(item.value(), Ok(Data::Image(frames)) if frames.is_empty()));
+    joined.retain(|index, _| images.iter().any(|item| item.index() == *index));
+    (images, joined)
+}
@@ -0,0 +1,131 @@
+use printpdf::{BuiltinFont, DictItem, Op, Pt, TextItem};
+
+const FONT: BuiltinFont = BuiltinFont::Helvetica;
+/// Written in place of characters WinAnsiEncoding has no code for.
+const REPLACEMENT: u8 = b'?';
+/// First code with a glyph, the space.
+const FIRST_CODE: u8 = 0x20;
+/// Widths of the glyphs of the WinAnsi codes from [`FIRST_CODE`] on, in thousandths of the font size, as given
+/// by the AFM file of Helvetica. Codes without a glyph are 0.
+#[rustfmt::skip]
+const WIDTHS: [u16; 224] = [
+    // 0x20
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    // 0x40
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    // 0x60
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584, 0,
+    // 0x80
+    556, 0, 222, 556, 333, 1000, 556, 556, 333, 1000, 667, 333, 1000, 0, 611, 0,
+    0, 222, 222, 333, 333, 350, 556, 1000, 333, 1000, 500, 333, 944, 0, 500, 667,
+    // 0xA0
+    278, 333, 556, 556, 556, 556, 260, 556, 333, 737, 370, 556, 584, 333, 737, 333,
+    400, 584, 333, 333, 333, 556, 537, 278, 333, 333, 365, 556, 834, 834, 834, 611,
+    // 0xC0
+    667, 667, 667, 667, 667, 667, 1000, 722, 667, 667, 667, 667, 278, 278, 278, 278,
+    722, 722, 778, 778, 778, 778, 778, 584, 778, 722, 722, 722, 722, 667, 667, 611,
+    // 0xE0
+    556, 556, 556, 556, 556, 556, 889, 500, 556, 556, 556, 556, 278, 278, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 584, 611, 556, 556, 556, 556, 500, 556, 500,
+];
+/// Characters of the WinAnsi codes from 0x80 to 0x9F, which differ from Latin-1.
+#[rustfmt::skip]
+const WINDOWS_CODES: [(char, u8); 27] = [
+    ('€', 0x80), ('‚', 0x82), ('ƒ', 0x83), ('„', 0x84), ('…', 0x85), ('†', 0x86), ('‡', 0x87),
+    ('ˆ', 0x88), ('‰', 0x89), ('Š', 0x8A), ('‹', 0x8B), ('Œ', 0x8C), ('Ž', 0x8E), ('‘', 0x91),
+    ('’', 0x92), ('“', 0x93), ('”', 0x94), ('•', 0x95), ('–', 0x96), ('—', 0x97), ('˜', 0x98),
+    ('™', 0x99), ('š', 0x9A), ('›', 0x9B), ('œ', 0x9C), ('ž', 0x9E), ('Ÿ', 0x9F),
+];
+
+/// Text encoded in WinAnsiEncoding, which Helvetica is written with.
+pub(crate) struct Encoded {
+    pub bytes: Vec<u8>,
+    /// Whether some characters had no code and were replaced with `?`.
+    pub replaced: bool,
+}
+
+/// Encodes the text, replacing characters without a code with `?`.
+pub(crate) fn encode(text: &str) -> Encoded {
+    let mut replaced = false;
+    let bytes = text
+        .chars()
+        .map(|c| {
+            code(c).unwrap_or_else(|| {
+                replaced = true;
+                REPLACEMENT
+            })
+        })
+        .collect();
+    Encoded { bytes, replaced }
+}
+
+fn code(c: char) -> Option<u8> {
+    match c as u32 {
+        0x20..=0x7E | 0xA0..=0xFF => Some(c as u8),
+        _ => WINDOWS_CODES
+            .iter()
+            .find(|(windows, _)| *windows == c)
+            .map(|(_, code)| *code),
+    }
+}
+
+/// Width of the encoded text, relative to the font size.
+pub(crate) fn width(bytes: &[u8]) -> f64 {
+    let thousandths: u32 = bytes
+        .iter()
+        .filter_map(|code| WIDTHS.get(code.checked_sub(FIRST_CODE)? as usize))
+        .map(|&width| width as u32)
+        .sum();
+    thousandths as f64 / 1000.0
+}
+
+/// Operations writing the encoded text with the font set by [`set_font_op`].
+///
+/// printpdf writes text in built-in fonts as UTF-8, while they are read as WinAnsi, so the bytes are written
+/// with a plain `Tj`. It comes after an empty text, which makes printpdf add the font to the page.
+pub(crate) fn write_ops(bytes: Vec<u8>) -> [Op; 2] {
+    [
+        Op::WriteTextBuiltinFont {
+            items: vec![TextItem::Text(String::new())],
+            font: FONT,
+        },
+        Op::Unknown {
+            key: "Tj".to_owned(),
+            value: vec![DictItem::String {
+                data: bytes,
+                literal: false,
+            }],
+        },
+    ]
+}
+
+pub(crate) fn set_font_op(size: f32) -> Op {
+    Op::SetFontSizeBuiltinFont {
+        size: Pt(size),
+        font: FONT,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_win_ansi() {
+        let encoded = encode("café – 100€");
+        assert_eq!(encoded.bytes, b"caf\xE9 \x96 100\x80");
+        assert!(!encoded.replaced);
+        let encoded = encode("Łódź");
+        assert_eq!(encoded.bytes, b"?\xF3d?");
+        assert!(encoded.replaced);
+    }
+
+    #[test]
+    fn measures_text() {
+        assert_eq!(width(b"Wi"), 1.166);
+        assert_eq!(width(&encode("é").bytes), width(b"e"));
+    }
+}
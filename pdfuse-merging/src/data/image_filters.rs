@@ -0,0 +1,233 @@
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Pixel};
+use pdfuse_parameters::ImageFilter;
+
+use super::LoadedImage;
+use crate::error::ImageLoadError;
+
+/// Part of the darkest and of the lightest pixels left out when looking for black and white by auto-levels.
+const CLIPPED: f64 = 0.01;
+/// Side of the neighborhood compared with by adaptive threshold, as a part of the shorter side of the image.
+const NEIGHBORHOOD: f64 = 1.0 / 16.0;
+/// How much darker than its neighborhood a pixel has to be to turn black.
+const THRESHOLD_OFFSET: f64 = 10.0;
+const SHARPEN_SIGMA: f32 = 1.5;
+const SHARPEN_THRESHOLD: i32 = 4;
+
+/// Applies the filters in order, leaving an image with 8 bits per component.
+pub(crate) fn apply(
+    image: &mut LoadedImage,
+    filters: &[ImageFilter],
+) -> Result<(), ImageLoadError> {
+    if filters.is_empty() {
+        return Ok(());
+    }
+    let mut filtered = to_8_bit(image.visible()?.into_owned());
+    for filter in filters {
+        filtered = match filter {
+            ImageFilter::Grayscale => filtered.grayscale(),
+            ImageFilter::AutoLevels => auto_levels(filtered),
+            ImageFilter::AdaptiveThreshold => adaptive_threshold(&filtered.to_luma8()).into(),
+            ImageFilter::Despeckle => despeckle(filtered),
+            ImageFilter::Sharpen => filtered.unsharpen(SHARPEN_SIGMA, SHARPEN_THRESHOLD),
+        };
+    }
+    image.set_filtered(filtered);
+    Ok(())
+}
+
+fn to_8_bit(image: DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(_)
+        | DynamicImage::ImageLumaA8(_)
+        | DynamicImage::ImageRgb8(_)
+        | DynamicImage::ImageRgba8(_) => image,
+        DynamicImage::ImageLuma16(_) => image.to_luma8().into(),
+        DynamicImage::ImageLumaA16(_) => image.to_luma_alpha8().into(),
+        other => match other.color().has_alpha() {
+            true => other.to_rgba8().into(),
+            false => other.to_rgb8().into(),
+        },
+    }
+}
+
+/// Stretches brightness linearly, so that the darkest and the lightest pixels become black and white.
+///
+/// The same stretch is applied to every color channel, which keeps the hues.
+fn auto_levels(image: DynamicImage) -> DynamicImage {
+    let gray = image.to_luma8();
+    let mut histogram = [0u64; 256];
+    for pixel in gray.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let clipped = (gray.len() as f64 * CLIPPED) as u64;
+    let black = clipped_level(&histogram, 0..256, clipped);
+    let white = clipped_level(&histogram, (0..256).rev(), clipped);
+    if white <= black {
+        return image;
+    }
+    let scale = 255.0 / (white - black) as f64;
+    let table: Vec<u8> = (0..256)
+        .map(|level| {
+            ((level as f64 - black as f64) * scale)
+                .round()
+                .clamp(0.0, 255.0) as u8
+        })
+        .collect();
+    map_colors(image, |value| table[value as usize])
+}
+
+/// First of the levels, in the given order, with more than `clipped` pixels up to it.
+fn clipped_level(
+    histogram: &[u64; 256],
+    levels: impl Iterator<Item = usize>,
+    clipped: u64,
+) -> usize {
+    let mut seen = 0;
+    for level in levels {
+        seen += histogram[level];
+        if seen > clipped {
+            return level;
+        }
+    }
+    0
+}
+
+/// Changes every color channel of an 8-bit image, leaving alpha as it is.
+fn map_colors(image: DynamicImage, change: impl Fn(u8) -> u8) -> DynamicImage {
+    fn map<P: Pixel<Subpixel = u8>>(
+        mut buffer: ImageBuffer<P, Vec<u8>>,
+        change: impl Fn(u8) -> u8,
+    ) -> ImageBuffer<P, Vec<u8>> {
+        for pixel in buffer.pixels_mut() {
+            pixel.apply_without_alpha(&change);
+        }
+        buffer
+    }
+    match image {
+        DynamicImage::ImageLuma8(buffer) => map(buffer, change).into(),
+        DynamicImage::ImageLumaA8(buffer) => map(buffer, change).into(),
+        DynamicImage::ImageRgb8(buffer) => map(buffer, change).into(),
+        DynamicImage::ImageRgba8(buffer) => map(buffer, change).into(),
+        other => map(other.to_rgba8(), change).into(),
+    }
+}
+
+/// Black where a pixel is darker than the mean of its neighborhood, white elsewhere.
+fn adaptive_threshold(image: &GrayImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let radius = ((width.min(height) as f64 * NEIGHBORHOOD) as u32 / 2).max(1);
+    // sums of all pixels above and to the left, with an extra row and column of zeros
+    let stride = width as usize + 1;
+    let mut sums = vec![0u64; stride * (height as usize + 1)];
+    for y in 0..height as usize {
+        let mut row_sum = 0;
+        for x in 0..width as usize {
+            row_sum += image.get_pixel(x as u32, y as u32).0[0] as u64;
+            sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row_sum;
+        }
+    }
+    GrayImage::from_fn(width, height, |x, y| {
+        let (left, top) = (
+            x.saturating_sub(radius) as usize,
+            y.saturating_sub(radius) as usize,
+        );
+        let right = (x + radius + 1).min(width) as usize;
+        let bottom = (y + radius + 1).min(height) as usize;
+        let sum = sums[bottom * stride + right] + sums[top * stride + left]
+            - sums[top * stride + right]
+            - sums[bottom * stride + left];
+        let mean = sum as f64 / ((right - left) * (bottom - top)) as f64;
+        let value = image.get_pixel(x, y).0[0] as f64;
+        Luma([if value < mean - THRESHOLD_OFFSET {
+            0
+        } else {
+            255
+        }])
+    })
+}
+
+/// Median of each pixel and its eight neighbors, for every color channel.
+fn despeckle(image: DynamicImage) -> DynamicImage {
+    fn median<P: Pixel<Subpixel = u8>>(
+        buffer: &ImageBuffer<P, Vec<u8>>,
+    ) -> ImageBuffer<P, Vec<u8>> {
+        let (width, height) = buffer.dimensions();
+        let mut result = buffer.clone();
+        let alpha = P::HAS_ALPHA.then(|| P::CHANNEL_COUNT as usize - 1);
+        // the neighborhood is at most 3 by 3, smaller at the edges
+        let mut values = [0u8; 9];
+        for (x, y, pixel) in result.enumerate_pixels_mut() {
+            let rows = y.saturating_sub(1)..(y + 2).min(height);
+            let columns = x.saturating_sub(1)..(x + 2).min(width);
+            let count = rows.len() * columns.len();
+            for (channel, value) in pixel.channels_mut().iter_mut().enumerate() {
+                if Some(channel) == alpha {
+                    continue;
+                }
+                let neighbors = rows
+                    .clone()
+                    .flat_map(|ny| columns.clone().map(move |nx| (nx, ny)))
+                    .map(|(nx, ny)| buffer.get_pixel(nx, ny).channels()[channel]);
+                for (slot, neighbor) in values.iter_mut().zip(neighbors) {
+                    *slot = neighbor;
+                }
+                let (_, median, _) = values[..count].select_nth_unstable(count / 2);
+                *value = *median;
+            }
+        }
+        result
+    }
+    match image {
+        DynamicImage::ImageLuma8(buffer) => median(&buffer).into(),
+        DynamicImage::ImageLumaA8(buffer) => median(&buffer).into(),
+        DynamicImage::ImageRgb8(buffer) => median(&buffer).into(),
+        DynamicImage::ImageRgba8(buffer) => median(&buffer).into(),
+        other => median(&other.to_rgba8()).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+
+    #[test]
+    fn stretches_levels() {
+        let image = RgbImage::from_fn(10, 10, |x, _| match x {
+            0..5 => Rgb([60, 50, 70]),
+            _ => Rgb([190, 180, 200]),
+        });
+        let stretched = auto_levels(image.into());
+        let brightness = stretched.to_luma8();
+        assert!(brightness.get_pixel(0, 0).0[0] < 10);
+        assert!(brightness.get_pixel(9, 0).0[0] > 245);
+        // blue stays the strongest channel of the dark color
+        let [red, green, blue] = stretched.to_rgb8().get_pixel(0, 0).0;
+        assert!(blue > red && red > green);
+    }
+
+    #[test]
+    fn thresholds_against_surroundings() {
+        // a dark stroke on a background getting darker to the right, like a shadow
+        let image = GrayImage::from_fn(64, 64, |x, y| {
+            let background = 230 - x as u8 * 2;
+            Luma([if y == 32 { background - 60 } else { background }])
+        });
+        let thresholded = adaptive_threshold(&image);
+        for x in [2, 32, 61] {
+            assert_eq!(thresholded.get_pixel(x, 32).0, [0]);
+            assert_eq!(thresholded.get_pixel(x, 10).0, [255]);
+        }
+    }
+
+    #[test]
+    fn removes_specks() {
+        let mut image = GrayImage::from_pixel(9, 9, Luma([255]));
+        image.put_pixel(4, 4, Luma([0]));
+        // corners have only four pixels around them
+        image.put_pixel(0, 0, Luma([0]));
+        let cleaned = despeckle(image.into()).to_luma8();
+        assert!(cleaned.pixels().all(|p| p.0 == [255]));
+    }
+}
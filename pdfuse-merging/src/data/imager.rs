@@ -1,20 +1,27 @@
 
-use image::{imageops::FilterType, DynamicImage};
-use lopdf::Document;
-use pdfuse_sizing::{CustomSize, Length, Size};
+use std::{collections::HashMap, path::Path};
+
+use image::DynamicImage;
+use lopdf::{Document, Stream};
+use pdfuse_parameters::{Alignment, AutoOrient, CaptionPosition, Grid, PlacementMode};
+use pdfuse_sizing::{CustomSize, Length, Margins, Size};
 use pdfuse_utils::debug_t;
+use rayon::prelude::*;
 // use lopdf::Document, Image, ImageTransform, ImageXObject, PdfDocumentReference, PdfLayerReference,
 use printpdf::ImageCompression;
 use printpdf::{
-    ImageOptimizationOptions, PdfDocument, PdfPage, RawImageData, RawImageFormat,
+    ImageOptimizationOptions, LinePoint, Op, PaintMode, PdfDocument, PdfPage, Point, Polygon,
+    PolygonRing, RawImageData, RawImageFormat, WindingOrder, XObjectTransform,
 };
 use printpdf::{PdfSaveOptions, PdfWarnMsg, RawImage};
 
 use crate::error::ImageLoadError;
 
-use super::LoadedImage;
+use super::{
+    bilevel, caption::Caption, cmyk, color_profile, jpeg2000::Jpeg2000Data, mirror_margins, placeholder, resolution::Resolution, LoadedImage,
+};
 
-fn dynamic_to_pdf(image: DynamicImage) -> Result<RawImage, ImageLoadError> {
+pub(crate) fn dynamic_to_pdf(image: DynamicImage) -> Result<RawImage, ImageLoadError> {
     // yoinked from printpdf
     // I couldn't find anything to create image from already loaded image.
     let width = image.width() as usize;
@@ -59,10 +66,31 @@ pub struct Imager {
     pub(crate) document: PdfDocument,
     pub(crate) page_size: CustomSize,
     pub(crate) dpi: f64,
-    pub(crate) margin: CustomSize,
+    pub(crate) margin: Margins,
+    /// Whether left and right margins swap on the backs of sheets, see [`mirror_margins`].
+    pub(crate) mirror_margins: bool,
     pub(crate) pages: Vec<PdfPage>,
     pub(crate) quality: u8,
     pub(crate) lossless: bool,
+    pub(crate) auto_orient: Option<AutoOrient>,
+    /// Smallest and largest page, when pages match their images instead of using `page_size`.
+    pub(crate) image_page_limits: Option<(CustomSize, CustomSize)>,
+    /// Whether images are sized by the resolution saved in them rather than `dpi`.
+    pub(crate) use_image_dpi: bool,
+    /// Whether images are turned black and white and compressed with CCITT Group 4.
+    pub(crate) bilevel: bool,
+    /// Gray level below which pixels turn black, chosen for each image if `None`.
+    pub(crate) bilevel_threshold: Option<u8>,
+    /// Line of text written next to each image.
+    pub(crate) caption: Option<Caption>,
+    /// Cells of the pages and the space between them, when images share pages.
+    pub(crate) grid: Option<(Grid, Length)>,
+    /// Contents of the filled cells of the grid page being laid out.
+    pub(crate) grid_cells: Vec<Vec<Op>>,
+    /// Image XObjects that printpdf cannot create, by the name of their placeholder image.
+    pub(crate) streams: HashMap<String, Stream>,
+    /// ICC color profiles of the images, by the name of the image.
+    pub(crate) icc_profiles: HashMap<String, Vec<u8>>,
 }
 impl Imager {
     pub fn close_and_into_document(mut self) -> Document {
@@ -70,7 +98,8 @@ impl Imager {
         let save_options = PdfSaveOptions {
             optimize: true,
             subset_fonts: true,
-            secure: true,
+            // captions are written with operations printpdf does not know, see [`helvetica::write_ops`]
+            secure: false,
             image_optimization: Some(ImageOptimizationOptions {
                 quality: Some(self.quality as f32 / 100.0),
                 max_image_size: Some("2137gb".to_string()), // "arbitrarily" large size -> we resize the image by ourselves
@@ -97,18 +126,27 @@ impl Imager {
             If uncompressed(!) image would exceed the size, scale it down
 
          */
+        self.finish_grid_page();
         let mut warnings: Vec<PdfWarnMsg> = vec![];
         let bytes = self
             .document
             .with_pages(self.pages)
             .save(&save_options, &mut warnings);
-        Document::load_mem(&bytes).unwrap()
+        let mut document = Document::load_mem(&bytes).unwrap();
+        placeholder::replace(&mut document, self.streams);
+        color_profile::embed_profiles(&mut document, self.icc_profiles);
+        let shift = (self.margin.right - self.margin.left).pt();
+        // pages matching their images have no margins
+        if self.mirror_margins && self.image_page_limits.is_none() && shift != 0.0 {
+            mirror_margins::mark_pages(&mut document, shift);
+        }
+        document
     }
     pub fn new<FloatLike, PageLike>(
         title: &str,
         page_size: PageLike,
         dpi: FloatLike,
-        margin: CustomSize,
+        margin: Margins,
         quality: u8,
         lossless: bool,
     ) -> Self
@@ -121,48 +159,499 @@ impl Imager {
             page_size: page_size.into(),
             dpi: dpi.into(),
             margin,
+            mirror_margins: false,
             pages: vec![],
             quality,
             lossless,
+            auto_orient: None,
+            image_page_limits: None,
+            use_image_dpi: false,
+            bilevel: false,
+            bilevel_threshold: None,
+            caption: None,
+            grid: None,
+            grid_cells: vec![],
+            streams: HashMap::new(),
+            icc_profiles: HashMap::new(),
+        }
+    }
+
+    /// Turns pages or images which would fit better the other way, see [`AutoOrient`].
+    pub fn with_auto_orient(mut self, auto_orient: Option<AutoOrient>) -> Self {
+        self.auto_orient = auto_orient;
+        self
+    }
+
+    /// Swaps the left and right margin of the image pages which end up on the backs of sheets.
+    pub fn with_mirrored_margins(mut self, mirror_margins: bool) -> Self {
+        self.mirror_margins = mirror_margins;
+        self
+    }
+
+    /// Writes a caption made from the template beneath or above each image, taking room from the drawing area.
+    ///
+    /// The caption uses the font in `font_bytes`, which is embedded, or Helvetica.
+    pub fn with_caption(
+        mut self,
+        template: Option<String>,
+        position: CaptionPosition,
+        font_size: f32,
+        font_bytes: Option<&[u8]>,
+    ) -> Self {
+        self.caption = template.map(|template| {
+            Caption::new(template, position, font_size, font_bytes, &mut self.document)
+        });
+        self
+    }
+
+    /// Lays images out in the cells of the grid, row by row, with `gutter` between the cells.
+    ///
+    /// The grid fills the drawing area of `page_size` and takes precedence over pages matching images.
+    pub fn with_grid(mut self, grid: Option<Grid>, gutter: Length) -> Self {
+        self.grid = grid.map(|grid| (grid, gutter));
+        self
+    }
+
+    /// Adds the grid page being laid out, if any of its cells are filled.
+    fn finish_grid_page(&mut self) {
+        if self.grid_cells.is_empty() {
+            return;
         }
+        let contents = self.grid_cells.drain(..).flatten().collect();
+        self.pages.push(PdfPage::new(
+            self.page_size.horizontal.into(),
+            self.page_size.vertical.into(),
+            contents,
+        ));
     }
 
-    pub fn add_image(&mut self, image: LoadedImage) -> Result<(), ImageLoadError> {
-        let page_size = self.page_size;
-        let page_with_margins = page_size - self.margin;
+    /// Makes each page the size of its image at the DPI, without margins, scaled to stay within the limits.
+    pub fn with_page_matching_image(mut self, min: CustomSize, max: CustomSize) -> Self {
+        self.image_page_limits = Some((min, max));
+        self
+    }
+
+    /// Sizes images by the resolution saved in them, falling back to the DPI for images without one.
+    pub fn with_image_dpi(mut self, use_image_dpi: bool) -> Self {
+        self.use_image_dpi = use_image_dpi;
+        self
+    }
+
+    /// Compresses images as black and white scans, see [`bilevel::to_fax_stream`].
+    pub fn with_bilevel_compression(mut self, bilevel: bool, threshold: Option<u8>) -> Self {
+        self.bilevel = bilevel;
+        self.bilevel_threshold = threshold;
+        self
+    }
+
+    /// Resolution giving the physical size of the image.
+    fn resolution_of(&self, image: &LoadedImage) -> Resolution {
+        image
+            .resolution()
+            .filter(|_| self.use_image_dpi)
+            .unwrap_or((self.dpi, self.dpi))
+    }
+
+    /// Adds a JPEG 2000 image, embedded without decoding it, on a page or in the next cell of the grid.
+    ///
+    /// The size comes from the resolution saved in the file, or from the DPI if there is none. As the pixels are
+    /// not decoded, the image is never turned, and one filling its area is clipped to it instead of cropped.
+    pub fn add_jpeg2000(
+        &mut self,
+        image: Jpeg2000Data,
+        placement: PlacementMode,
+        alignment: Alignment,
+    ) {
+        let resolution = image.resolution().unwrap_or((self.dpi, self.dpi));
+        let natural_size = get_size_at_resolution(image.width(), image.height(), resolution);
+        let caption_text = self
+            .caption
+            .as_ref()
+            .map(|caption| caption.text_for(image.source_path(), None, 1));
+        let mut layout = self.lay_out(natural_size, caption_text, placement, alignment);
+        let placed_points = placed_points(
+            image.width(),
+            image.height(),
+            resolution,
+            &mut layout,
+            false,
+        );
+        self.place(PreparedImage {
+            pixels: Pixels::Stream(image.into_stream()),
+            icc_profile: None,
+            placed_points,
+            layout,
+        });
+    }
+
+    /// Adds the images in order, each sized and placed as `placement_for` its file says.
+    ///
+    /// The pages are laid out one by one, then the images are turned, cropped, downsampled and encoded in parallel.
+    /// Images that fail are left out, the others taking their places, and returned with their positions in `images`.
+    pub fn add_images(
+        &mut self,
+        images: Vec<LoadedImage>,
+        placement_for: impl Fn(&Path) -> (PlacementMode, Alignment),
+    ) -> Vec<(usize, ImageLoadError)> {
+        let layouts: Vec<Layout> = images
+            .iter()
+            .map(|image| {
+                let (placement, alignment) = placement_for(image.source_path());
+                let natural_size = get_size_at_resolution(
+                    image.width(),
+                    image.height(),
+                    self.resolution_of(image),
+                );
+                let caption_text = self.caption.as_ref().map(|caption| caption.text(image));
+                self.lay_out(natural_size, caption_text, placement, alignment)
+            })
+            .collect();
+        let encoding = self.encoding();
+        let prepared: Vec<Result<PreparedImage, ImageLoadError>> = images
+            .into_par_iter()
+            .zip(layouts)
+            .map(|(image, layout)| encoding.prepare(image, layout))
+            .collect();
+        let mut failed = vec![];
+        for (position, image) in prepared.into_iter().enumerate() {
+            match image {
+                Ok(image) => self.place(image),
+                Err(err) => failed.push((position, err)),
+            }
+        }
+        failed
+    }
 
-        let adjusted_image = adjust_to_dpi(image, page_with_margins, self.dpi);
+    /// Chooses the page an image of `natural_size` goes on and the area it fills.
+    ///
+    /// Cells of a grid are all the same size, so images are laid out in the first one and moved to theirs by
+    /// [`Imager::place`].
+    fn lay_out(
+        &self,
+        natural_size: CustomSize,
+        caption_text: Option<String>,
+        placement: PlacementMode,
+        alignment: Alignment,
+    ) -> Layout {
+        // a cell of a grid is drawn like a page with the rest of the page as its margins
+        let (mut page_size, page_margin) = match (self.grid, self.image_page_limits) {
+            (Some((grid, gutter)), _) => {
+                let margin = cell_margins(self.page_size, self.margin, grid, gutter, 0);
+                (self.page_size, margin)
+            }
+            (None, Some((min, max))) => (natural_size.clamped(&min, &max), Margins::zero()),
+            (None, None) => (self.page_size, self.margin),
+        };
+        let matching_page = self.grid.is_none() && self.image_page_limits.is_some();
+        // the image is placed in what is left of the drawing area by the caption
+        let margin = match &self.caption {
+            Some(caption) => {
+                // a page matching the image grows to keep the image at its size
+                if matching_page {
+                    page_size.vertical += caption.height();
+                }
+                caption.reserve(page_margin)
+            }
+            None => page_margin,
+        };
+        let mut turn_image = false;
+        // a page matching the image is always oriented the right way
+        if let (Some(auto_orient), false) = (self.auto_orient, matching_page) {
+            let area = page_size - margin.total();
+            // pages of a grid are shared, so only the image can turn
+            let turned_area = match self.grid {
+                Some(_) => area.transposed(),
+                None => page_size.transposed() - margin.total(),
+            };
+            if turned_area.fit_size(&natural_size) > area.fit_size(&natural_size) {
+                match (auto_orient, self.grid) {
+                    (AutoOrient::Page, None) => page_size.transpose(),
+                    (AutoOrient::Page, Some(_)) | (AutoOrient::Image, _) => turn_image = true,
+                }
+            }
+        }
+        Layout {
+            page_size,
+            margin,
+            page_margin,
+            turn_image,
+            placement,
+            alignment,
+            caption_text,
+        }
+    }
 
-        let image_size = get_image_size(&adjusted_image, self.dpi);
+    fn encoding(&self) -> Encoding {
+        Encoding {
+            dpi: self.dpi,
+            use_image_dpi: self.use_image_dpi,
+            quality: self.quality,
+            lossless: self.lossless,
+            bilevel: self.bilevel,
+            bilevel_threshold: self.bilevel_threshold,
+        }
+    }
 
-        let pdf_image = dynamic_to_pdf(adjusted_image)?;
-        
-        let image_id = self.document.add_image(&pdf_image);
-        let scale = page_with_margins.fit_size(&image_size);
-        let translation = get_image_translation(page_size, image_size * scale, self.margin);
-        // debug!("{scale}");
-        let image_contents = printpdf::Op::UseXobject {
-            id: image_id,
-            transform: printpdf::XObjectTransform {
-                scale_x: Some(scale as f32),
-                scale_y: Some(scale as f32),
-                dpi: Some(self.dpi as f32),
+    /// Adds the image to the document and draws it on a page of its own or in the next cell of the grid.
+    fn place(&mut self, prepared: PreparedImage) {
+        let PreparedImage {
+            pixels,
+            icc_profile,
+            placed_points,
+            layout,
+        } = prepared;
+        let Layout {
+            page_size,
+            mut margin,
+            mut page_margin,
+            alignment,
+            ..
+        } = layout;
+        // the image moves from the first cell of the grid to the next free one
+        if let Some((grid, gutter)) = self.grid {
+            let cell = self.grid_cells.len() as u32;
+            page_margin = cell_margins(self.page_size, self.margin, grid, gutter, cell);
+            margin = match &self.caption {
+                Some(caption) => caption.reserve(page_margin),
+                None => page_margin,
+            };
+        }
+        let (image_id, width, height) = match pixels {
+            Pixels::Stream(stream) => {
+                let image_id = self.document.add_image(&placeholder::image());
+                self.streams.insert(image_id.0.clone(), stream);
+                (image_id, 1, 1)
+            }
+            Pixels::Raw(pdf_image) => {
+                let (width, height) = (pdf_image.width, pdf_image.height);
+                (self.document.add_image(&pdf_image), width, height)
+            }
+        };
+        if let Some(profile) = icc_profile {
+            self.icc_profiles.insert(image_id.0.clone(), profile);
+        }
+        // one pixel is one point at 72 DPI, so the scale stretches the pixels to the placed size
+        let scale_x = placed_points.0 / width as f64;
+        let scale_y = placed_points.1 / height as f64;
+        let placed_size = CustomSize::from_points(placed_points.0, placed_points.1);
+        let translation = get_image_translation(page_size, placed_size, margin, alignment);
+        let use_image = |translation: CustomSize| Op::UseXobject {
+            id: image_id.clone(),
+            transform: XObjectTransform {
+                scale_x: Some(scale_x as f32),
+                scale_y: Some(scale_y as f32),
+                dpi: Some(72.0),
                 translate_x: Some(translation.horizontal.into()),
                 translate_y: Some(translation.vertical.into()),
                 rotate: None,
             },
         };
-        let page = PdfPage::new(
-            page_size.horizontal.into(),
-            page_size.vertical.into(),
-            vec![image_contents],
-        );
-        self.pages.push(page);
-        Ok(())
+        let mut image_contents = match layout.placement {
+            PlacementMode::Fit | PlacementMode::Fill | PlacementMode::Stretch => {
+                vec![use_image(translation)]
+            }
+            PlacementMode::ActualSize => {
+                clipped_to_area(page_size, margin, vec![use_image(translation)])
+            }
+            PlacementMode::Tile => {
+                let tiles = tile_positions(page_size, placed_size, margin, translation)
+                    .into_iter()
+                    .map(use_image)
+                    .collect();
+                clipped_to_area(page_size, margin, tiles)
+            }
+        };
+        if let (Some(caption), Some(text)) = (&self.caption, layout.caption_text) {
+            image_contents.extend(caption.ops(&text, page_size, page_margin));
+        }
+        match self.grid {
+            Some((grid, _)) => {
+                self.grid_cells.push(image_contents);
+                if self.grid_cells.len() as u64 == grid.cells() {
+                    self.finish_grid_page();
+                }
+            }
+            None => {
+                let page = PdfPage::new(
+                    page_size.horizontal.into(),
+                    page_size.vertical.into(),
+                    image_contents,
+                );
+                self.pages.push(page);
+            }
+        }
+    }
+}
+
+/// Where and how an image goes, chosen by [`Imager::lay_out`].
+struct Layout {
+    page_size: CustomSize,
+    /// Margins around the image, with the room for the caption.
+    margin: Margins,
+    /// Margins of the page or cell, without the room for the caption.
+    page_margin: Margins,
+    /// Whether the image is turned to fit its area better.
+    turn_image: bool,
+    placement: PlacementMode,
+    alignment: Alignment,
+    caption_text: Option<String>,
+}
+
+/// Settings of an [`Imager`] used to prepare the pixels, which unlike the document can be shared between threads.
+#[derive(Clone, Copy)]
+struct Encoding {
+    dpi: f64,
+    use_image_dpi: bool,
+    quality: u8,
+    lossless: bool,
+    bilevel: bool,
+    bilevel_threshold: Option<u8>,
+}
+
+impl Encoding {
+    fn resolution_of(&self, image: &LoadedImage) -> Resolution {
+        image
+            .resolution()
+            .filter(|_| self.use_image_dpi)
+            .unwrap_or((self.dpi, self.dpi))
+    }
+
+    /// Turns, crops and downsamples the image to fill its layout and encodes it for the document.
+    fn prepare(
+        &self,
+        mut image: LoadedImage,
+        mut layout: Layout,
+    ) -> Result<PreparedImage, ImageLoadError> {
+        if layout.turn_image {
+            image.rotate_left()?;
+        }
+        let page_with_margins = layout.page_size - layout.margin.total();
+        let icc_profile = image.take_icc_profile();
+
+        let resolution = self.resolution_of(&image);
+        if layout.placement == PlacementMode::Fill {
+            crop_to_area(&mut image, page_with_margins, resolution, layout.alignment)?;
+        }
+        // the physical size stays the same when the image is downsampled below
+        let placed_points =
+            placed_points(image.width(), image.height(), resolution, &mut layout, true);
+        match layout.placement {
+            PlacementMode::Fit | PlacementMode::Fill => {
+                adjust_to_dpi(&mut image, page_with_margins, self.dpi)?
+            }
+            PlacementMode::Stretch => stretch_to_dpi(&mut image, page_with_margins, self.dpi)?,
+            PlacementMode::ActualSize | PlacementMode::Tile => {}
+        };
+
+        // only images looking like scanned documents turn black and white, photos are kept as they are
+        let bilevel = self.bilevel && bilevel::looks_bilevel(&*image.visible()?);
+        if self.bilevel && !bilevel {
+            debug_t!(
+                "debug.bilevel_skipped",
+                path = image.source_path().display()
+            );
+        }
+        // the original file is used only if its pixels were not changed, in which case they are never decoded
+        let jpeg = match bilevel {
+            true => None,
+            false => image.take_jpeg(),
+        };
+        if jpeg.is_none() {
+            image.decode()?;
+        }
+        // known once the pixels are decoded
+        let is_cmyk = image.is_cmyk();
+        let adjusted_image = match jpeg {
+            Some(_) => None,
+            None => Some(image.into_parts()?.0),
+        };
+        let stream = match (jpeg, &adjusted_image) {
+            (Some(jpeg), _) => Some(jpeg.into_stream()),
+            (None, Some(adjusted_image)) if bilevel => Some(match adjusted_image {
+                DynamicImage::ImageRgba8(cmyk) if is_cmyk => {
+                    bilevel::to_fax_stream(&cmyk::to_rgb(cmyk).into(), self.bilevel_threshold)
+                }
+                _ => bilevel::to_fax_stream(adjusted_image, self.bilevel_threshold),
+            }),
+            (None, Some(DynamicImage::ImageRgba8(cmyk))) if is_cmyk => {
+                Some(cmyk::to_stream(cmyk, self.quality, self.lossless))
+            }
+            (None, Some(adjusted_image)) => bilevel::to_stream(adjusted_image),
+            (None, None) => None,
+        };
+        let pixels = match (stream, adjusted_image) {
+            (Some(stream), _) => Pixels::Stream(stream),
+            (None, Some(adjusted_image)) => Pixels::Raw(dynamic_to_pdf(adjusted_image)?),
+            (None, None) => return Err(ImageLoadError::UnknownFormat),
+        };
+        Ok(PreparedImage {
+            pixels,
+            // black and white pixels no longer have the colors of the profile
+            icc_profile: icc_profile.filter(|_| !bilevel),
+            placed_points,
+            layout,
+        })
     }
 }
 
-fn adjust_to_dpi(image: LoadedImage, draw_area: CustomSize, dpi: f64) -> DynamicImage {
+/// Pixels of an image ready to be added to the document.
+enum Pixels {
+    /// An image XObject printpdf cannot create, see [`Imager::streams`].
+    Stream(Stream),
+    Raw(RawImage),
+}
+
+/// An image encoded by [`Encoding::prepare`], waiting to be put on its page by [`Imager::place`].
+struct PreparedImage {
+    pixels: Pixels,
+    icc_profile: Option<Vec<u8>>,
+    /// Size of the image on the page, kept out of `Length`, which rounds to tenths of a millimeter.
+    placed_points: (f64, f64),
+    layout: Layout,
+}
+
+/// Size in points of an image of `width` by `height` pixels at `resolution` placed in the area of its layout.
+///
+/// An image filling its area which was not `cropped` to it covers the area, and is then placed at that size so
+/// that it gets clipped to the area.
+fn placed_points(
+    width: u32,
+    height: u32,
+    resolution: Resolution,
+    layout: &mut Layout,
+    cropped: bool,
+) -> (f64, f64) {
+    let area = layout.page_size - layout.margin.total();
+    // kept out of `Length`, which rounds to tenths of a millimeter
+    let image_points = (
+        width as f64 * 72.0 / resolution.0,
+        height as f64 * 72.0 / resolution.1,
+    );
+    let area_points = (area.horizontal.pt(), area.vertical.pt());
+    let (scale_x, scale_y) = match layout.placement {
+        PlacementMode::Fill if !cropped => {
+            let scale = (area_points.0 / image_points.0).max(area_points.1 / image_points.1);
+            layout.placement = PlacementMode::ActualSize;
+            (scale, scale)
+        }
+        PlacementMode::Fit | PlacementMode::Fill => {
+            let scale = (area_points.0 / image_points.0).min(area_points.1 / image_points.1);
+            (scale, scale)
+        }
+        PlacementMode::Stretch => (
+            area_points.0 / image_points.0,
+            area_points.1 / image_points.1,
+        ),
+        PlacementMode::ActualSize | PlacementMode::Tile => (1.0, 1.0),
+    };
+    (image_points.0 * scale_x, image_points.1 * scale_y)
+}
+
+fn adjust_to_dpi(
+    image: &mut LoadedImage,
+    draw_area: CustomSize,
+    dpi: f64,
+) -> Result<(), ImageLoadError> {
     let horizontal_pixel_max = draw_area.horizontal.inch() * dpi;
     let vertical_pixel_max = draw_area.vertical.inch() * dpi;
     let image_width = image.width() as f64;
@@ -173,39 +662,254 @@ fn adjust_to_dpi(image: LoadedImage, draw_area: CustomSize, dpi: f64) -> Dynamic
     if scale >= 1.0 {
         let target_dpi = (image.width() as f64 / draw_area.horizontal.inch()) as u32;
         debug_t!("debug.excess_dpi", dpi = target_dpi);
-        return image.into();
+        return Ok(());
     }
-    let (dynamic_image, path_buf): (DynamicImage, std::path::PathBuf) = image.into_parts();
     debug_t!(
         "debug.resizing_image",
-        name = path_buf.file_name().unwrap().to_string_lossy(),
-        width = dynamic_image.width(),
-        height = dynamic_image.height(),
+        name = image.source_path().file_name().unwrap().to_string_lossy(),
+        width = image.width(),
+        height = image.height(),
         target_width = horizontal_pixel_max as u32,
         target_height = vertical_pixel_max as u32,
         scale = scale
     );
-    dynamic_image.resize(
-        horizontal_pixel_max as u32,
-        vertical_pixel_max as u32,
-        FilterType::Lanczos3,
+    image.resize(horizontal_pixel_max as u32, vertical_pixel_max as u32)
+}
+/// Downsamples each side of the image separately, so that it is not denser than `dpi` when stretched over the area.
+fn stretch_to_dpi(
+    image: &mut LoadedImage,
+    draw_area: CustomSize,
+    dpi: f64,
+) -> Result<(), ImageLoadError> {
+    let horizontal_pixel_max = (draw_area.horizontal.inch() * dpi) as u32;
+    let vertical_pixel_max = (draw_area.vertical.inch() * dpi) as u32;
+    let target_width = image.width().min(horizontal_pixel_max).max(1);
+    let target_height = image.height().min(vertical_pixel_max).max(1);
+    if (target_width, target_height) == (image.width(), image.height()) {
+        return Ok(());
+    }
+    image.resize_exact(target_width, target_height)
+}
+
+/// Crops the image to the aspect ratio of the area, keeping the part given by the alignment.
+fn crop_to_area(
+    image: &mut LoadedImage,
+    draw_area: CustomSize,
+    resolution: Resolution,
+    alignment: Alignment,
+) -> Result<(), ImageLoadError> {
+    let (width, height) = (image.width() as f64, image.height() as f64);
+    // pixels are not square when the resolution differs between the directions
+    let (horizontal_dpi, vertical_dpi) = resolution;
+    let area_ratio = draw_area.horizontal / draw_area.vertical * horizontal_dpi / vertical_dpi;
+    if !area_ratio.is_normal() {
+        return Ok(());
+    }
+    let (crop_width, crop_height) = if width / height > area_ratio {
+        (height * area_ratio, height)
+    } else {
+        (width, width / area_ratio)
+    };
+    let (horizontal, vertical) = alignment.factors();
+    // pixel rows are counted from the top, alignment from the bottom
+    let x = (width - crop_width) * horizontal;
+    let y = (height - crop_height) * (1.0 - vertical);
+    image.crop(
+        x.round() as u32,
+        y.round() as u32,
+        (crop_width.round() as u32).max(1),
+        (crop_height.round() as u32).max(1),
     )
 }
-fn get_image_size(image: &DynamicImage, dpi: f64) -> CustomSize {
+
+fn get_size_at_resolution(width: u32, height: u32, resolution: Resolution) -> CustomSize {
+    let (horizontal_dpi, vertical_dpi) = resolution;
     CustomSize {
-        horizontal: Length::from_inches(image.width() as f64 / dpi),
-        vertical: Length::from_inches(image.height() as f64 / dpi),
+        horizontal: Length::from_inches(width as f64 / horizontal_dpi),
+        vertical: Length::from_inches(height as f64 / vertical_dpi),
     }
 }
 fn get_image_translation(
     page_size: CustomSize,
     image_size: CustomSize,
-    margin: CustomSize,
+    margin: Margins,
+    alignment: Alignment,
 ) -> CustomSize {
-    let margined_size = page_size - margin;
+    let margined_size = page_size - margin.total();
     // starting from bottom left (xD?)
     let difference = margined_size - image_size;
-    let half_dif = difference / 2.0;
-    let half_margin = margin / 2.0;
-    half_dif + half_margin
+    let (horizontal, vertical) = alignment.factors();
+    margin.bottom_left()
+        + CustomSize {
+            horizontal: difference.horizontal * horizontal,
+            vertical: difference.vertical * vertical,
+        }
+}
+
+/// Margins around the cell at `position` of the grid, counted row by row from the top left.
+///
+/// The grid fills the page inside `margin`, with `gutter` between the cells.
+fn cell_margins(
+    page_size: CustomSize,
+    margin: Margins,
+    grid: Grid,
+    gutter: Length,
+    position: u32,
+) -> Margins {
+    let area = page_size - margin.total();
+    let cell = CustomSize {
+        horizontal: (area.horizontal - gutter * (grid.columns - 1)) / grid.columns,
+        vertical: (area.vertical - gutter * (grid.rows - 1)) / grid.rows,
+    };
+    let (column, row) = (position % grid.columns, position / grid.columns);
+    let left = margin.left + (cell.horizontal + gutter) * column;
+    let top = margin.top + (cell.vertical + gutter) * row;
+    Margins {
+        top,
+        right: page_size.horizontal - left - cell.horizontal,
+        bottom: page_size.vertical - top - cell.vertical,
+        left,
+    }
+}
+
+/// Positions of tiles covering the drawing area, laid out from the aligned `origin` in every direction.
+fn tile_positions(
+    page_size: CustomSize,
+    tile_size: CustomSize,
+    margin: Margins,
+    origin: CustomSize,
+) -> Vec<CustomSize> {
+    let start = margin.bottom_left();
+    let end = page_size - margin.top_right();
+    let offsets = |origin: Length, tile: Length, start: Length, end: Length| -> Vec<Length> {
+        if tile.pt() <= 0.0 {
+            return vec![origin];
+        }
+        let first = ((start - origin) / tile).floor() as i64;
+        let last = ((end - origin) / tile).ceil() as i64;
+        (first..last).map(|i| origin + tile * i as f64).collect()
+    };
+    let columns = offsets(
+        origin.horizontal,
+        tile_size.horizontal,
+        start.horizontal,
+        end.horizontal,
+    );
+    let rows = offsets(origin.vertical, tile_size.vertical, start.vertical, end.vertical);
+    rows.iter()
+        .flat_map(|&vertical| {
+            columns.iter().map(move |&horizontal| CustomSize {
+                horizontal,
+                vertical,
+            })
+        })
+        .collect()
+}
+
+/// Wraps the operations so that they only draw inside the margins.
+fn clipped_to_area(page_size: CustomSize, margin: Margins, ops: Vec<Op>) -> Vec<Op> {
+    let start = margin.bottom_left();
+    let end = page_size - margin.top_right();
+    let point = |x: Length, y: Length| LinePoint {
+        p: Point {
+            x: x.into(),
+            y: y.into(),
+        },
+        bezier: false,
+    };
+    let clip = Polygon {
+        rings: vec![PolygonRing {
+            points: vec![
+                point(start.horizontal, start.vertical),
+                point(end.horizontal, start.vertical),
+                point(end.horizontal, end.vertical),
+                point(start.horizontal, end.vertical),
+            ],
+        }],
+        mode: PaintMode::Clip,
+        winding_order: WindingOrder::NonZero,
+    };
+    let mut clipped = vec![Op::SaveGraphicsState, Op::DrawPolygon { polygon: clip }];
+    clipped.extend(ops);
+    clipped.push(Op::RestoreGraphicsState);
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(horizontal: f64, vertical: f64) -> CustomSize {
+        CustomSize {
+            horizontal: Length::from_points(horizontal),
+            vertical: Length::from_points(vertical),
+        }
+    }
+
+    fn points(size: CustomSize) -> (i64, i64) {
+        (size.horizontal.pt().round() as i64, size.vertical.pt().round() as i64)
+    }
+
+    #[test]
+    fn translation_follows_alignment() {
+        let page = size(200.0, 100.0);
+        let image = size(50.0, 50.0);
+        let margin = Margins::from(size(20.0, 20.0));
+        let translation =
+            |alignment| points(get_image_translation(page, image, margin, alignment));
+        assert_eq!(translation(Alignment::BottomLeft), (10, 10));
+        assert_eq!(translation(Alignment::Center), (75, 25));
+        assert_eq!(translation(Alignment::TopRight), (140, 40));
+    }
+
+    #[test]
+    fn translation_keeps_uneven_margins() {
+        let page = size(200.0, 100.0);
+        let image = size(50.0, 50.0);
+        let margin = Margins {
+            top: Length::from_points(10.0),
+            right: Length::from_points(20.0),
+            bottom: Length::from_points(0.0),
+            left: Length::from_points(50.0),
+        };
+        let translation =
+            |alignment| points(get_image_translation(page, image, margin, alignment));
+        assert_eq!(translation(Alignment::BottomLeft), (50, 0));
+        assert_eq!(translation(Alignment::Center), (90, 20));
+        assert_eq!(translation(Alignment::TopRight), (130, 40));
+        let mirrored = get_image_translation(page, image, margin.mirrored(), Alignment::Center);
+        assert_eq!(points(mirrored), (60, 20));
+    }
+
+    #[test]
+    fn cells_fill_grid() {
+        let page = size(200.0, 100.0);
+        let margin = Margins::from(size(20.0, 20.0));
+        let grid = Grid { columns: 2, rows: 2 };
+        let gutter = Length::from_points(10.0);
+        let sides = |position| {
+            let cell = cell_margins(page, margin, grid, gutter, position);
+            [cell.left, cell.top, cell.right, cell.bottom].map(|x| x.pt().round() as i64)
+        };
+        assert_eq!(sides(0), [10, 10, 105, 55]);
+        assert_eq!(sides(1), [105, 10, 10, 55]);
+        assert_eq!(sides(3), [105, 55, 10, 10]);
+    }
+
+    #[test]
+    fn tiles_cover_area() {
+        let page = size(100.0, 100.0);
+        let tile = size(30.0, 40.0);
+        let margin = Margins::zero();
+        let origin = get_image_translation(page, tile, margin, Alignment::Center);
+        let tiles: Vec<(i64, i64)> = tile_positions(page, tile, margin, origin)
+            .into_iter()
+            .map(points)
+            .collect();
+        // columns at -25, 5, 35, 65, 95 and rows at -10, 30, 70
+        assert_eq!(tiles.len(), 15);
+        assert!(tiles.contains(&points(origin)));
+        assert!(tiles.contains(&(-25, -10)));
+        assert!(tiles.contains(&(95, 70)));
+    }
 }
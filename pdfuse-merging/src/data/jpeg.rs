@@ -0,0 +1,179 @@
+use lopdf::{dictionary, Stream};
+
+/// Frame markers of Huffman-coded JPEGs with 8-bit samples: baseline, extended and progressive.
+/// Lossless and arithmetic-coded JPEGs are left for the decoder.
+const SUPPORTED_FRAMES: [u8; 3] = [0xC0, 0xC1, 0xC2];
+const START_OF_SCAN: u8 = 0xDA;
+const ADOBE_SEGMENT: u8 = 0xEE;
+
+/// An encoded JPEG file, which can be embedded as it is with `/DCTDecode`.
+#[derive(Clone)]
+pub(crate) struct JpegData {
+    bytes: Vec<u8>,
+    width: u32,
+    height: u32,
+    components: u8,
+    /// Adobe files store CMYK inverted.
+    adobe: bool,
+}
+
+impl JpegData {
+    /// Reads the frame header of the file.
+    ///
+    /// Gives the bytes back if the file is not a JPEG that PDF readers can show without decoding it first.
+    pub fn parse(bytes: Vec<u8>) -> Result<JpegData, Vec<u8>> {
+        match read_frame_header(&bytes) {
+            Some((width, height, components, adobe)) => Ok(JpegData {
+                bytes,
+                width,
+                height,
+                components,
+                adobe,
+            }),
+            None => Err(bytes),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn components(&self) -> u8 {
+        self.components
+    }
+
+    /// Whether the file has an Adobe segment, whose CMYK is stored inverted.
+    pub fn adobe(&self) -> bool {
+        self.adobe
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Image XObject holding the file.
+    pub fn into_stream(self) -> Stream {
+        let color_space = match self.components {
+            1 => "DeviceGray",
+            4 => "DeviceCMYK",
+            _ => "DeviceRGB",
+        };
+        let mut dictionary = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => self.width as i64,
+            "Height" => self.height as i64,
+            "ColorSpace" => color_space,
+            "BitsPerComponent" => 8,
+            "Filter" => "DCTDecode",
+        };
+        if self.components == 4 && self.adobe {
+            dictionary.set(
+                "Decode",
+                vec![
+                    1.into(),
+                    0.into(),
+                    1.into(),
+                    0.into(),
+                    1.into(),
+                    0.into(),
+                    1.into(),
+                    0.into(),
+                ],
+            );
+        }
+        Stream::new(dictionary, self.bytes).with_compression(false)
+    }
+}
+
+/// Width, height, number of components and whether there is an Adobe segment, from the frame header.
+fn read_frame_header(bytes: &[u8]) -> Option<(u32, u32, u8, bool)> {
+    if bytes.get(0..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut adobe = false;
+    let mut position = 2;
+    loop {
+        if *bytes.get(position)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(position + 1)?;
+        position += 2;
+        match marker {
+            // fill bytes and markers without a segment
+            0xFF => position -= 1,
+            0x01 | 0xD0..=0xD7 => {}
+            START_OF_SCAN => return None,
+            _ => {
+                let length =
+                    u16::from_be_bytes([*bytes.get(position)?, *bytes.get(position + 1)?]) as usize;
+                let segment = bytes.get(position + 2..position + length)?;
+                if marker == ADOBE_SEGMENT && segment.starts_with(b"Adobe") {
+                    adobe = true;
+                }
+                if (0xC0..=0xCF).contains(&marker) && ![0xC4, 0xC8, 0xCC].contains(&marker) {
+                    // precision, height, width, number of components
+                    let [precision, h1, h0, w1, w0, components, ..] = *segment else {
+                        return None;
+                    };
+                    let height = u16::from_be_bytes([h1, h0]) as u32;
+                    let width = u16::from_be_bytes([w1, w0]) as u32;
+                    let supported = SUPPORTED_FRAMES.contains(&marker)
+                        && precision == 8
+                        && height > 0
+                        && matches!(components, 1 | 3 | 4);
+                    return supported.then_some((width, height, components, adobe));
+                }
+                position += length;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{codecs::jpeg::JpegEncoder, ExtendedColorType};
+
+    use super::*;
+
+    fn encode(width: u32, height: u32, color: ExtendedColorType, channels: usize) -> Vec<u8> {
+        let mut bytes = Cursor::new(vec![]);
+        let pixels = vec![128; width as usize * height as usize * channels];
+        JpegEncoder::new(&mut bytes)
+            .encode(&pixels, width, height, color)
+            .unwrap();
+        bytes.into_inner()
+    }
+
+    #[test]
+    fn reads_frame_header() {
+        let rgb = JpegData::parse(encode(7, 5, ExtendedColorType::Rgb8, 3)).unwrap();
+        assert_eq!((rgb.width(), rgb.height(), rgb.components), (7, 5, 3));
+        let gray = JpegData::parse(encode(2, 9, ExtendedColorType::L8, 1)).unwrap();
+        assert_eq!((gray.width(), gray.height(), gray.components), (2, 9, 1));
+        let stream = gray.into_stream();
+        assert_eq!(
+            stream.dict.get(b"ColorSpace").unwrap().as_name().unwrap(),
+            b"DeviceGray"
+        );
+        assert_eq!(
+            stream.dict.get(b"Filter").unwrap().as_name().unwrap(),
+            b"DCTDecode"
+        );
+    }
+
+    #[test]
+    fn rejects_other_data() {
+        assert!(JpegData::parse(b"\x89PNG\r\n".to_vec()).is_err());
+        assert!(JpegData::parse(vec![0xFF, 0xD8, 0xFF]).is_err());
+        let mut truncated = encode(4, 4, ExtendedColorType::Rgb8, 3);
+        truncated.truncate(30);
+        assert!(JpegData::parse(truncated).is_err());
+    }
+}
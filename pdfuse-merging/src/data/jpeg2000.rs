@@ -0,0 +1,212 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use lopdf::{dictionary, Stream};
+
+use super::resolution::{self, Resolution, METERS_PER_INCH};
+
+/// Signature box every JP2 file starts with.
+const SIGNATURE: [u8; 12] = [
+    0x00, 0x00, 0x00, 0x0C, b'j', b'P', b' ', b' ', 0x0D, 0x0A, 0x87, 0x0A,
+];
+
+/// A JP2 or JPX file, which is embedded as it is with `/JPXDecode`, as it cannot be decoded.
+pub(crate) struct Jpeg2000Data {
+    bytes: Vec<u8>,
+    source_path: PathBuf,
+    width: u32,
+    height: u32,
+    resolution: Option<Resolution>,
+}
+
+/// Reads the whole file if it starts with the JP2 signature box, and only its start otherwise.
+pub(crate) fn read_if_signed(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut file = File::open(path)?;
+    let mut bytes = vec![0; SIGNATURE.len()];
+    // files shorter than the signature are left to the other formats as well
+    if file.read_exact(&mut bytes).is_err() || bytes != SIGNATURE {
+        return Ok(None);
+    }
+    file.read_to_end(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+impl Jpeg2000Data {
+    /// Reads the image header and the resolution from the header box of the file.
+    ///
+    /// Returns `None` if the file has no signature, no image header or no codestream.
+    pub fn parse(bytes: Vec<u8>, source_path: &Path) -> Option<Jpeg2000Data> {
+        if !bytes.starts_with(&SIGNATURE) {
+            return None;
+        }
+        let mut header = None;
+        let mut has_codestream = false;
+        for (kind, contents) in boxes(&bytes) {
+            match &kind {
+                b"jp2h" => header = Some(contents),
+                b"jp2c" => has_codestream = true,
+                _ => {}
+            }
+        }
+        let header = header.filter(|_| has_codestream)?;
+        let (_, image_header) = boxes(header).find(|(kind, _)| kind == b"ihdr")?;
+        let height = u32::from_be_bytes(image_header.get(0..4)?.try_into().ok()?);
+        let width = u32::from_be_bytes(image_header.get(4..8)?.try_into().ok()?);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        // the display resolution is meant for showing the image, the capture one is that of the scanner
+        let resolution = boxes(header)
+            .find(|(kind, _)| kind == b"res ")
+            .and_then(|(_, resolutions)| {
+                let read = |wanted: &[u8; 4]| {
+                    boxes(resolutions)
+                        .find(|(kind, _)| kind == wanted)
+                        .and_then(|(_, contents)| read_resolution(contents))
+                };
+                read(b"resd").or_else(|| read(b"resc"))
+            })
+            .and_then(resolution::plausible);
+        Some(Jpeg2000Data {
+            bytes,
+            source_path: source_path.to_owned(),
+            width,
+            height,
+            resolution,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn resolution(&self) -> Option<Resolution> {
+        self.resolution
+    }
+
+    pub fn source_path(&self) -> &Path {
+        &self.source_path
+    }
+
+    /// Image XObject holding the file.
+    ///
+    /// The color space and the bits per component are left to the file, as `/JPXDecode` allows.
+    pub fn into_stream(self) -> Stream {
+        let dictionary = dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => self.width as i64,
+            "Height" => self.height as i64,
+            "Filter" => "JPXDecode",
+        };
+        Stream::new(dictionary, self.bytes).with_compression(false)
+    }
+}
+
+/// Boxes of a file or of a superbox, as their type and contents.
+fn boxes(mut bytes: &[u8]) -> impl Iterator<Item = ([u8; 4], &[u8])> {
+    std::iter::from_fn(move || {
+        let length = u32::from_be_bytes(bytes.get(0..4)?.try_into().ok()?) as u64;
+        let kind: [u8; 4] = bytes.get(4..8)?.try_into().ok()?;
+        // a length of 0 runs to the end, a length of 1 is followed by the actual 64-bit length
+        let (header_length, length) = match length {
+            0 => (8, bytes.len() as u64),
+            1 => (16, u64::from_be_bytes(bytes.get(8..16)?.try_into().ok()?)),
+            _ => (8, length),
+        };
+        let length = usize::try_from(length).ok()?;
+        let contents = bytes.get(header_length..length)?;
+        bytes = &bytes[length..];
+        Some((kind, contents))
+    })
+}
+
+/// Reads a resolution box: vertical and horizontal grid points per meter, each as a numerator, a denominator
+/// and a power of ten.
+fn read_resolution(contents: &[u8]) -> Option<Resolution> {
+    let [vn1, vn0, vd1, vd0, hn1, hn0, hd1, hd0, ve, he]: [u8; 10] =
+        contents.get(0..10)?.try_into().ok()?;
+    let per_inch = |numerator: [u8; 2], denominator: [u8; 2], exponent: u8| {
+        let denominator = u16::from_be_bytes(denominator);
+        (denominator > 0).then(|| {
+            let per_meter = u16::from_be_bytes(numerator) as f64 / denominator as f64
+                * 10f64.powi(exponent as i8 as i32);
+            per_meter * METERS_PER_INCH
+        })
+    };
+    Some((
+        per_inch([hn1, hn0], [hd1, hd0], he)?,
+        per_inch([vn1, vn0], [vd1, vd0], ve)?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jp2_box(kind: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+        let mut bytes = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend(kind);
+        bytes.extend(contents);
+        bytes
+    }
+
+    /// A file with a 300 by 200 image header, the given resolution boxes and an empty codestream.
+    fn file(resolutions: &[u8]) -> Vec<u8> {
+        let mut image_header = 200u32.to_be_bytes().to_vec();
+        image_header.extend(300u32.to_be_bytes());
+        image_header.extend([0, 3, 7, 7, 0, 0]);
+        let mut header = jp2_box(b"ihdr", &image_header);
+        if !resolutions.is_empty() {
+            header.extend(jp2_box(b"res ", resolutions));
+        }
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(jp2_box(b"ftyp", b"jp2 \0\0\0\0jp2 "));
+        bytes.extend(jp2_box(b"jp2h", &header));
+        bytes.extend(jp2_box(b"jp2c", &[]));
+        bytes
+    }
+
+    /// A resolution box with the same number of grid points per meter in both directions.
+    fn resolution_box(kind: &[u8; 4], numerator: u16, denominator: u16, exponent: i8) -> Vec<u8> {
+        let mut contents = vec![];
+        for _ in 0..2 {
+            contents.extend(numerator.to_be_bytes());
+            contents.extend(denominator.to_be_bytes());
+        }
+        contents.extend([exponent as u8; 2]);
+        jp2_box(kind, &contents)
+    }
+
+    #[test]
+    fn reads_header() {
+        let plain = Jpeg2000Data::parse(file(&[]), Path::new("a.jp2")).unwrap();
+        assert_eq!(
+            (plain.width(), plain.height(), plain.resolution()),
+            (300, 200, None)
+        );
+        // 11811 points per meter are 300 DPI; the capture resolution is only used without a display one
+        let mut resolutions = resolution_box(b"resc", 11811, 1, 0);
+        let captured = Jpeg2000Data::parse(file(&resolutions), Path::new("a.jp2")).unwrap();
+        assert_eq!(captured.resolution().unwrap().0.round(), 300.0);
+        resolutions.extend(resolution_box(b"resd", 5906, 10, 1));
+        let displayed = Jpeg2000Data::parse(file(&resolutions), Path::new("a.jp2")).unwrap();
+        assert_eq!(displayed.resolution().unwrap().1.round(), 150.0);
+    }
+
+    #[test]
+    fn rejects_incomplete_files() {
+        let mut without_codestream = file(&[]);
+        without_codestream.truncate(without_codestream.len() - 8);
+        assert!(Jpeg2000Data::parse(without_codestream, Path::new("a.jp2")).is_none());
+        assert!(Jpeg2000Data::parse(SIGNATURE.to_vec(), Path::new("a.jp2")).is_none());
+        assert!(Jpeg2000Data::parse(b"\xFF\xD8\xFF\xE0".to_vec(), Path::new("a.jp2")).is_none());
+    }
+}
@@ -1,12 +1,13 @@
 use pdfuse_sizing::{CustomSize, Length};
 use pdfuse_utils::{debug_t, error_t};
-use lopdf::Document;
+use lopdf::{Document, ObjectId};
 use std::{
     fmt::{Debug, Display},
     path::{Path, PathBuf},
     process::Command,
 };
 
+use super::page_tree::materialize_inherited_attributes;
 use crate::error::{DocumentLoadError, LibreConversionError};
 
 #[derive(Debug)]
@@ -39,31 +40,10 @@ impl LoadedDocument {
     pub fn page_size(&self) -> Option<CustomSize> {
         let mut page_size: Option<CustomSize> = None;
         for page in self.data.page_iter() {
-            let Ok(media_box_array) = self
-                .data
-                .get_object(page)
-                .and_then(|p| p.as_dict())
-                .and_then(|d| d.get(b"MediaBox"))
-                .and_then(|mb| mb.as_array())
-            else {
-                debug_t!("debug.invalid_mediabox", document = self);
-                continue;
-            };
-            // all sizes in points
-            let x_min = media_box_array[0].as_float().unwrap_or_default();
-            let y_min = media_box_array[1].as_float().unwrap_or_default();
-            let x_max = media_box_array[2].as_float().unwrap_or_default();
-            let y_max = media_box_array[3].as_float().unwrap_or_default();
-            let horizontal = Length::from_points(x_max - x_min);
-            let vertical = Length::from_points(y_max - y_min);
-            if horizontal <= Length::zero() || vertical <= Length::zero() {
-                debug_t!("debug.zero_mediabox", document = self);
-                continue;
+            match media_box_size(&self.data, page) {
+                Some(size) => page_size = Some(size),
+                None => debug_t!("debug.invalid_mediabox", document = self),
             }
-            page_size = Some(CustomSize {
-                horizontal,
-                vertical,
-            });
         }
         if page_size.is_none(){
             error_t!("error.invalid_mediabox",document=self);
@@ -71,14 +51,46 @@ impl LoadedDocument {
         page_size
     }
     pub fn load_pdf(path: &Path) -> Result<LoadedDocument, DocumentLoadError> {
-        Document::load(path)
-            .map(|data| LoadedDocument {
-                data: Box::new(data),
-                source_path: path.to_path_buf(),
-            })
-            .map_err(Into::into)
+        let mut data = Document::load(path)?;
+        // so that page sizes can be read from the pages alone
+        for page in data.get_pages().into_values() {
+            materialize_inherited_attributes(&mut data, page);
+        }
+        Ok(LoadedDocument {
+            data: Box::new(data),
+            source_path: path.to_path_buf(),
+        })
     }
 }
+/// Reads the size of the media box of the given page.
+///
+/// Returns `None` if the media box is missing, malformed or has no area.
+pub(crate) fn media_box_size(document: &Document, page: ObjectId) -> Option<CustomSize> {
+    let media_box_array = document
+        .get_object(page)
+        .and_then(|p| p.as_dict())
+        .and_then(|d| d.get(b"MediaBox"))
+        .and_then(|mb| mb.as_array())
+        .ok()?;
+    if media_box_array.len() < 4 {
+        return None;
+    }
+    // all sizes in points
+    let x_min = media_box_array[0].as_float().unwrap_or_default();
+    let y_min = media_box_array[1].as_float().unwrap_or_default();
+    let x_max = media_box_array[2].as_float().unwrap_or_default();
+    let y_max = media_box_array[3].as_float().unwrap_or_default();
+    let horizontal = Length::from_points(x_max - x_min);
+    let vertical = Length::from_points(y_max - y_min);
+    if horizontal <= Length::zero() || vertical <= Length::zero() {
+        return None;
+    }
+    Some(CustomSize {
+        horizontal,
+        vertical,
+    })
+}
+
 pub fn convert_document_to_pdf(
     document_path: &Path,
     libre_exe_path: &Path,
@@ -1,38 +1,493 @@
-use std::path::{Path, PathBuf};
+use std::{
+    borrow::Cow,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
 
-use image::{DynamicImage, ImageReader};
+use chrono::NaiveDateTime;
+use image::{
+    codecs::{gif::GifDecoder, jpeg::JpegDecoder, webp::WebPDecoder},
+    imageops::FilterType,
+    metadata::Orientation,
+    AnimationDecoder, DynamicImage, GenericImageView, ImageDecoder, ImageFormat, ImageReader,
+};
+use pdfuse_parameters::{ColorProfiles, Parameters};
+
+use super::{
+    cmyk, color_profile, exif,
+    jpeg::JpegData,
+    resolution::{self, Resolution},
+    scan_cleanup,
+    tiff_pages::TiffPages,
+    tone_map,
+};
+use crate::{error::ImageLoadError, DocumentLoadError};
+
+/// Brightness below which a pixel is counted as ink.
+const INK_LEVEL: u8 = 160;
+/// Fraction of the width and height ignored at each edge, where scanners leave shadows.
+const IGNORED_EDGE: f32 = 0.05;
 
 pub struct LoadedImage {
-    image: Box<DynamicImage>,
+    /// The pixels, `None` while nothing needed them decoded from `jpeg`, see [`LoadedImage::decode`].
+    image: Option<Box<DynamicImage>>,
     source_path: PathBuf,
+    /// The original file, while the image still matches it.
+    jpeg: Option<JpegData>,
+    /// Resolution saved in the file, if it is plausible.
+    resolution: Option<Resolution>,
+    /// ICC color profile describing the colors of the image.
+    icc_profile: Option<Vec<u8>>,
+    /// The four channels of the image hold cyan, magenta, yellow and black instead of RGBA.
+    cmyk: bool,
+    /// Time the photo was taken, from its EXIF data.
+    date_taken: Option<NaiveDateTime>,
+    /// Number of the page or frame within its file, counted from 1.
+    page_number: usize,
+    /// Index of the input the image comes from, see [`LoadedImage::load_frames`].
+    input_index: usize,
 }
-impl From<LoadedImage> for DynamicImage {
-    fn from(value: LoadedImage) -> Self {
-        value.into_parts().0
+impl TryFrom<LoadedImage> for DynamicImage {
+    type Error = ImageLoadError;
+
+    /// The image in colors that can be shown, with CMYK turned into RGB.
+    fn try_from(mut value: LoadedImage) -> Result<Self, Self::Error> {
+        match value.cmyk {
+            true => Ok(value.visible()?.into_owned()),
+            false => Ok(value.into_parts()?.0),
+        }
     }
 }
 impl LoadedImage {
     pub fn width(&self) -> u32 {
-        self.image.width()
+        match (&self.image, &self.jpeg) {
+            (Some(image), _) => image.width(),
+            (None, Some(jpeg)) => jpeg.width(),
+            (None, None) => 0,
+        }
     }
-    pub fn into_parts(self) -> (DynamicImage, PathBuf) {
-        (*self.image, self.source_path)
+    /// The pixels as they are kept, which are CMYK for [`LoadedImage::is_cmyk`] images.
+    pub fn into_parts(mut self) -> Result<(DynamicImage, PathBuf), ImageLoadError> {
+        self.decode()?;
+        let image = self.image.take().ok_or(ImageLoadError::UnknownFormat)?;
+        Ok((*image, self.source_path))
     }
     pub fn height(&self) -> u32 {
-        self.image.height()
+        match (&self.image, &self.jpeg) {
+            (Some(image), _) => image.height(),
+            (None, Some(jpeg)) => jpeg.height(),
+            (None, None) => 0,
+        }
+    }
+    /// Decodes the JPEG file, unless it was decoded already.
+    ///
+    /// JPEGs which need not be turned upright are loaded without decoding them, so that they can be embedded
+    /// as they are when nothing changes their pixels.
+    pub(crate) fn decode(&mut self) -> Result<&mut DynamicImage, ImageLoadError> {
+        let image = match (self.image.take(), &self.jpeg) {
+            (Some(image), _) => image,
+            (None, Some(jpeg)) => {
+                let (pixels, cmyk) = decode_jpeg(jpeg)?;
+                self.cmyk = cmyk;
+                Box::new(pixels)
+            }
+            (None, None) => return Err(ImageLoadError::UnknownFormat),
+        };
+        Ok(self.image.insert(image))
+    }
+    /// Rotates the image by 90 degrees counterclockwise, so that its top ends up on the left.
+    pub fn rotate_left(&mut self) -> Result<(), ImageLoadError> {
+        let image = self.decode()?;
+        *image = image.rotate270();
+        self.jpeg = None;
+        self.resolution = self.resolution.map(|(horizontal, vertical)| (vertical, horizontal));
+        Ok(())
+    }
+    /// Keeps only the given rectangle of the image, in pixels from the top left corner.
+    pub fn crop(&mut self, x: u32, y: u32, width: u32, height: u32) -> Result<(), ImageLoadError> {
+        if (x, y, width, height) == (0, 0, self.width(), self.height()) {
+            return Ok(());
+        }
+        let image = self.decode()?;
+        *image = image.crop_imm(x, y, width, height);
+        self.jpeg = None;
+        Ok(())
+    }
+    /// Scales the image down to fit within the given size, keeping its aspect ratio.
+    pub(crate) fn resize(&mut self, width: u32, height: u32) -> Result<(), ImageLoadError> {
+        let image = self.decode()?;
+        *image = image.resize(width, height, FilterType::Lanczos3);
+        self.jpeg = None;
+        Ok(())
+    }
+    /// Scales the image to exactly the given size.
+    pub(crate) fn resize_exact(&mut self, width: u32, height: u32) -> Result<(), ImageLoadError> {
+        let image = self.decode()?;
+        *image = image.resize_exact(width, height, FilterType::Lanczos3);
+        self.jpeg = None;
+        Ok(())
+    }
+    /// Turns the image around its center so that lines tilted by `angle` degrees become level.
+    pub fn straighten(&mut self, angle: f64) -> Result<(), ImageLoadError> {
+        let image = self.decode()?;
+        *image = scan_cleanup::rotated(image, angle);
+        self.jpeg = None;
+        Ok(())
+    }
+    /// Replaces the pixels with the filtered image, whose colors can be shown as they are.
+    pub(crate) fn set_filtered(&mut self, image: DynamicImage) {
+        self.image = Some(Box::new(image));
+        self.jpeg = None;
+        self.cmyk = false;
+    }
+    pub fn is_cmyk(&self) -> bool {
+        self.cmyk
+    }
+    /// The image with CMYK turned into RGB.
+    pub(crate) fn visible(&mut self) -> Result<Cow<'_, DynamicImage>, ImageLoadError> {
+        let cmyk = self.cmyk;
+        Ok(match self.decode()? {
+            DynamicImage::ImageRgba8(pixels) if cmyk => Cow::Owned(cmyk::to_rgb(pixels).into()),
+            image => Cow::Borrowed(image),
+        })
+    }
+    /// Takes the original JPEG file, if the image was not changed since it was loaded.
+    pub(crate) fn take_jpeg(&mut self) -> Option<JpegData> {
+        self.jpeg.take()
+    }
+    /// Takes the ICC color profile of the image.
+    pub(crate) fn take_icc_profile(&mut self) -> Option<Vec<u8>> {
+        self.icc_profile.take()
+    }
+    /// Horizontal and vertical resolution saved in the file, in dots per inch.
+    pub fn resolution(&self) -> Option<Resolution> {
+        self.resolution
     }
     pub fn source_path(&self) -> &Path {
         self.source_path.as_path()
     }
-    pub fn load(path: impl AsRef<Path>) -> std::io::Result<LoadedImage> {
-        let image_reader =
-            ImageReader::open(path.as_ref()).and_then(|r| r.with_guessed_format())?;
-        let decoded_image = image_reader
-            .decode()
-            .expect("Format should be already successfully detected");
-        Ok(LoadedImage {
-            image: Box::new(decoded_image),
-            source_path: path.as_ref().to_path_buf(),
-        })
+    /// Time the photo was taken, as saved in its EXIF data.
+    pub fn date_taken(&self) -> Option<NaiveDateTime> {
+        self.date_taken
+    }
+    /// Number of the page or frame within its file, counted from 1.
+    pub fn page_number(&self) -> usize {
+        self.page_number
+    }
+    /// Index of the input the image comes from, which stays with it when it shares grid pages with other inputs.
+    pub fn input_index(&self) -> usize {
+        self.input_index
+    }
+    /// Percentage of the image covered with dark pixels, ignoring the edges.
+    pub fn ink_coverage(&mut self) -> Result<f32, ImageLoadError> {
+        let gray = self.visible()?.to_luma8();
+        let (width, height) = gray.dimensions();
+        let x = (width as f32 * IGNORED_EDGE) as u32;
+        let y = (height as f32 * IGNORED_EDGE) as u32;
+        let inner = gray.view(x, y, width - 2 * x, height - 2 * y);
+        let total = inner.width() as u64 * inner.height() as u64;
+        if total == 0 {
+            return Ok(0.0);
+        }
+        let ink = inner
+            .pixels()
+            .filter(|(_, _, pixel)| pixel.0[0] < INK_LEVEL)
+            .count();
+        Ok((ink as f64 * 100.0 / total as f64) as f32)
+    }
+    /// Decodes the image, turning it upright according to its EXIF orientation unless the parameters say otherwise.
+    ///
+    /// JPEGs are left undecoded when they can be embedded as they are, see [`LoadedImage::decode`].
+    pub fn load(
+        path: impl AsRef<Path>,
+        parameters: &Parameters,
+    ) -> Result<LoadedImage, DocumentLoadError> {
+        let bytes = fs::read(path.as_ref())?;
+        let format = ImageReader::new(Cursor::new(bytes.as_slice()))
+            .with_guessed_format()?
+            .format();
+        let bytes = match format {
+            Some(ImageFormat::Tiff) => {
+                let mut pages = TiffPages::new(&bytes, parameters.ignore_exif_orientation)?;
+                let (decoded, resolution) = pages.next().ok_or(ImageLoadError::UnknownFormat)??;
+                return Ok(LoadedImage::from_decoded(
+                    decoded,
+                    path.as_ref(),
+                    resolution,
+                    None,
+                    parameters,
+                ));
+            }
+            Some(ImageFormat::Jpeg) => match JpegData::parse(bytes) {
+                Ok(jpeg) => return Ok(LoadedImage::from_jpeg(jpeg, path.as_ref(), parameters)?),
+                // lossless and arithmetic-coded files are decoded like any other image
+                Err(bytes) => bytes,
+            },
+            _ => bytes,
+        };
+        let image_reader = ImageReader::new(Cursor::new(bytes.as_slice())).with_guessed_format()?;
+        let decoder = image_reader.into_decoder().map_err(ImageLoadError::from)?;
+        let decoded = decode_upright(decoder, parameters.ignore_exif_orientation)?;
+        let resolution =
+            resolution::read(&bytes, format).map(|x| resolution::turned(x, decoded.orientation));
+        Ok(LoadedImage::from_decoded(
+            decoded,
+            path.as_ref(),
+            resolution,
+            None,
+            parameters,
+        ))
+    }
+    /// Reads the headers of the JPEG, leaving its pixels undecoded unless they have to be turned upright,
+    /// converted to sRGB or read as CMYK.
+    fn from_jpeg(
+        jpeg: JpegData,
+        path: &Path,
+        parameters: &Parameters,
+    ) -> Result<LoadedImage, ImageLoadError> {
+        let mut decoder = JpegDecoder::new(Cursor::new(jpeg.bytes()))?;
+        let headers = Headers::read(&mut decoder, parameters.ignore_exif_orientation);
+        let resolution = resolution::read(jpeg.bytes(), Some(ImageFormat::Jpeg))
+            .map(|x| resolution::turned(x, headers.orientation));
+        // the frame header tells CMYK files apart, whose colors are never converted
+        let cmyk = jpeg.components() == 4;
+        let converted = parameters.color_profiles == ColorProfiles::ConvertToSrgb
+            && headers.icc_profile.is_some()
+            && !cmyk;
+        if headers.orientation.is_none() && !converted {
+            let mut image = LoadedImage {
+                image: None,
+                source_path: path.to_path_buf(),
+                jpeg: Some(jpeg),
+                resolution,
+                icc_profile: headers.icc_profile,
+                cmyk,
+                date_taken: headers.date_taken,
+                page_number: 1,
+                input_index: 0,
+            };
+            if parameters.color_profiles == ColorProfiles::ConvertToSrgb {
+                image.convert_to_srgb();
+            }
+            return Ok(image);
+        }
+        let (pixels, cmyk) = decode_jpeg(&jpeg)?;
+        let decoded = headers.upright(pixels, cmyk);
+        let jpeg = Some(jpeg).filter(|jpeg| {
+            decoded.orientation.is_none()
+                && (jpeg.width(), jpeg.height()) == (decoded.image.width(), decoded.image.height())
+        });
+        Ok(LoadedImage::from_decoded(
+            decoded, path, resolution, jpeg, parameters,
+        ))
+    }
+    /// Converts the colors to sRGB if the parameters ask for it.
+    fn from_decoded(
+        decoded: Decoded,
+        path: &Path,
+        resolution: Option<Resolution>,
+        jpeg: Option<JpegData>,
+        parameters: &Parameters,
+    ) -> LoadedImage {
+        let mut image = LoadedImage {
+            image: Some(Box::new(decoded.image)),
+            source_path: path.to_path_buf(),
+            jpeg,
+            resolution,
+            icc_profile: decoded.icc_profile,
+            cmyk: decoded.cmyk,
+            date_taken: decoded.date_taken,
+            page_number: 1,
+            input_index: 0,
+        };
+        if parameters.color_profiles == ColorProfiles::ConvertToSrgb {
+            image.convert_to_srgb();
+        }
+        image
+    }
+    /// Converts the colors from the ICC profile to sRGB and drops the profile.
+    ///
+    /// CMYK images and images whose profile cannot be used keep their profile, to be embedded with them.
+    fn convert_to_srgb(&mut self) {
+        let Some(profile) = self.icc_profile.as_deref().filter(|_| !self.cmyk) else {
+            return;
+        };
+        let converted = self
+            .image
+            .as_deref()
+            .and_then(|image| color_profile::to_srgb(image, profile));
+        if let Some(converted) = converted {
+            self.image = Some(Box::new(converted));
+            self.jpeg = None;
+            self.icc_profile = None;
+        }
     }
+    /// Decodes every page of multi-page TIFFs and every frame of animated GIFs and WebPs, one image for other formats,
+    /// of the input at `input_index`.
+    ///
+    /// Only the first frame is decoded with [`Parameters::first_frame_only`].
+    pub fn load_frames(
+        path: impl AsRef<Path>,
+        input_index: usize,
+        parameters: &Parameters,
+    ) -> Result<Vec<LoadedImage>, DocumentLoadError> {
+        let mut frames = Self::decode_frames(path.as_ref(), parameters)?;
+        for (frame, page_number) in frames.iter_mut().zip(1..) {
+            frame.page_number = page_number;
+            frame.input_index = input_index;
+        }
+        Ok(frames)
+    }
+    fn decode_frames(
+        path: &Path,
+        parameters: &Parameters,
+    ) -> Result<Vec<LoadedImage>, DocumentLoadError> {
+        if parameters.first_frame_only {
+            return Ok(vec![Self::load(path, parameters)?]);
+        }
+        let image_reader = ImageReader::open(path).and_then(|r| r.with_guessed_format())?;
+        let frames = match image_reader.format() {
+            Some(ImageFormat::Gif) => {
+                let decoder = GifDecoder::new(image_reader.into_inner()).map_err(ImageLoadError::from)?;
+                without_metadata(animation_frames(decoder)?)
+            }
+            Some(ImageFormat::WebP) => {
+                let decoder = WebPDecoder::new(image_reader.into_inner()).map_err(ImageLoadError::from)?;
+                match decoder.has_animation() {
+                    true => without_metadata(animation_frames(decoder)?),
+                    false => vec![],
+                }
+            }
+            Some(ImageFormat::Tiff) => {
+                let bytes = fs::read(path)?;
+                // pages are converted one by one, so that only one of them is held in its decoded form
+                return TiffPages::new(&bytes, parameters.ignore_exif_orientation)?
+                    .map(|page| {
+                        let (decoded, resolution) = page?;
+                        Ok(LoadedImage::from_decoded(
+                            decoded, path, resolution, None, parameters,
+                        ))
+                    })
+                    .collect();
+            }
+            _ => vec![],
+        };
+        if frames.is_empty() {
+            return Ok(vec![Self::load(path, parameters)?]);
+        }
+        Ok(frames
+            .into_iter()
+            .map(|(decoded, resolution)| {
+                LoadedImage::from_decoded(decoded, path, resolution, None, parameters)
+            })
+            .collect())
+    }
+}
+
+/// An image decoded by [`decode_upright`].
+pub(crate) struct Decoded {
+    pub image: DynamicImage,
+    /// The orientation applied to the image, if it had to be turned.
+    pub orientation: Option<Orientation>,
+    pub icc_profile: Option<Vec<u8>>,
+    /// Whether the image holds CMYK, see [`LoadedImage::is_cmyk`].
+    pub cmyk: bool,
+    pub date_taken: Option<NaiveDateTime>,
+}
+
+/// Decodes the image, turning it upright according to its EXIF orientation unless `ignore_orientation` is set.
+pub(crate) fn decode_upright(
+    mut decoder: impl ImageDecoder,
+    ignore_orientation: bool,
+) -> Result<Decoded, ImageLoadError> {
+    Headers::read(&mut decoder, ignore_orientation).decode(decoder)
+}
+
+/// What the headers of an image say, which is read before its pixels are decoded.
+struct Headers {
+    /// The orientation to apply, if the image has to be turned.
+    orientation: Option<Orientation>,
+    icc_profile: Option<Vec<u8>>,
+    date_taken: Option<NaiveDateTime>,
+}
+
+impl Headers {
+    fn read(decoder: &mut impl ImageDecoder, ignore_orientation: bool) -> Headers {
+        // a broken orientation tag is not worth failing the whole image
+        let orientation = decoder
+            .orientation()
+            .ok()
+            .filter(|x| *x != Orientation::NoTransforms && !ignore_orientation);
+        let icc_profile = decoder.icc_profile().ok().flatten();
+        let date_taken = decoder
+            .exif_metadata()
+            .ok()
+            .flatten()
+            .and_then(|chunk| exif::date_taken(&chunk));
+        Headers {
+            orientation,
+            icc_profile,
+            date_taken,
+        }
+    }
+
+    /// Decodes the pixels and turns them upright.
+    fn decode(self, decoder: impl ImageDecoder) -> Result<Decoded, ImageLoadError> {
+        let image = tone_map::to_8_bit(DynamicImage::from_decoder(decoder)?);
+        Ok(self.upright(image, false))
+    }
+
+    /// Turns the decoded pixels upright, see [`Decoded::cmyk`] for `cmyk`.
+    fn upright(self, mut image: DynamicImage, cmyk: bool) -> Decoded {
+        if let Some(orientation) = self.orientation {
+            image.apply_orientation(orientation);
+        }
+        Decoded {
+            image,
+            orientation: self.orientation,
+            icc_profile: self.icc_profile,
+            cmyk,
+            date_taken: self.date_taken,
+        }
+    }
+}
+
+/// Decodes the pixels of the JPEG, keeping CMYK for files with four components, and tells if they are CMYK.
+///
+/// Only the CMYK decoder reads four component files, the `image` crate is left the files it can turn into RGB.
+fn decode_jpeg(jpeg: &JpegData) -> Result<(DynamicImage, bool), ImageLoadError> {
+    if let Some(cmyk) = cmyk::decode_jpeg(jpeg) {
+        return Ok((cmyk.into(), true));
+    }
+    let decoder = JpegDecoder::new(Cursor::new(jpeg.bytes()))?;
+    Ok((DynamicImage::from_decoder(decoder)?, false))
+}
+
+/// Frames of animations, which carry neither a resolution nor a color profile.
+fn without_metadata(frames: Vec<DynamicImage>) -> Vec<(Decoded, Option<Resolution>)> {
+    frames
+        .into_iter()
+        .map(|image| {
+            let decoded = Decoded {
+                image,
+                orientation: None,
+                icc_profile: None,
+                cmyk: false,
+                date_taken: None,
+            };
+            (decoded, None)
+        })
+        .collect()
+}
+
+/// Decodes all frames of an animation, each composed onto the full canvas.
+fn animation_frames<'a>(
+    decoder: impl AnimationDecoder<'a>,
+) -> Result<Vec<DynamicImage>, ImageLoadError> {
+    let frames = decoder.into_frames().collect_frames()?;
+    Ok(frames
+        .into_iter()
+        .map(|frame| DynamicImage::ImageRgba8(frame.into_buffer()))
+        .collect())
 }
@@ -0,0 +1,116 @@
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Document, Object, ObjectId, Stream,
+};
+use pdfuse_utils::Indexed;
+
+/// Key of the page dictionary holding how far the page moves to the right on the back of a sheet, in points.
+///
+/// Set on image pages by [`mark_pages`] and removed by [`mirror`], so it never reaches the output file.
+const SHIFT_KEY: &[u8] = b"PdfuseMirrorShift";
+
+/// Marks all pages of the document to be moved by `shift` points when they end up on the back of a sheet.
+pub(crate) fn mark_pages(document: &mut Document, shift: f64) {
+    for page_id in document.get_pages().into_values() {
+        if let Ok(page) = document.get_dictionary_mut(page_id) {
+            page.set(SHIFT_KEY, shift as f32);
+        }
+    }
+}
+
+/// Moves the contents of the marked pages with even numbers in the merged output, so that their left and right
+/// margins are swapped, as needed for binding sheets printed on both sides.
+///
+/// Pages are numbered across all of the documents, in order.
+pub(crate) fn mirror(documents: &mut [Indexed<Document>]) {
+    let mut page_number = 0;
+    for document in documents.iter_mut().map(Indexed::value_mut) {
+        for page_id in document.get_pages().into_values() {
+            page_number += 1;
+            let shift = match document.get_dictionary_mut(page_id) {
+                Ok(page) => page.remove(SHIFT_KEY).and_then(|x| x.as_float().ok()),
+                Err(_) => None,
+            };
+            if let Some(shift) = shift.filter(|_| page_number % 2 == 0) {
+                shift_contents(document, page_id, shift);
+            }
+        }
+    }
+}
+
+/// Wraps the contents of the page in a horizontal translation by `shift` points.
+fn shift_contents(document: &mut Document, page_id: ObjectId, shift: f32) {
+    let content_stream = |operations| {
+        let content = Content { operations };
+        Stream::new(dictionary! {}, content.encode().unwrap_or_default())
+    };
+    let before = document.add_object(content_stream(vec![
+        Operation::new("q", vec![]),
+        Operation::new(
+            "cm",
+            vec![
+                1.into(),
+                0.into(),
+                0.into(),
+                1.into(),
+                shift.into(),
+                0.into(),
+            ],
+        ),
+    ]));
+    let after = document.add_object(content_stream(vec![Operation::new("Q", vec![])]));
+    let Ok(page) = document.get_dictionary_mut(page_id) else {
+        return;
+    };
+    let mut contents = vec![Object::Reference(before)];
+    match page.get(b"Contents") {
+        Ok(Object::Array(array)) => contents.extend(array.iter().cloned()),
+        Ok(other) => contents.push(other.clone()),
+        Err(_) => {}
+    }
+    contents.push(Object::Reference(after));
+    page.set("Contents", contents);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_documents::document_with_pages;
+
+    fn contents(document: &Document, page_number: u32) -> Object {
+        let page_id = document.get_pages()[&page_number];
+        document
+            .get_dictionary(page_id)
+            .unwrap()
+            .get(b"Contents")
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn shifts_marked_even_pages() {
+        // an unmarked page first, so the marked pages are the second and third of the output
+        let mut images = document_with_pages(2);
+        mark_pages(&mut images, 30.0);
+        let mut documents = vec![
+            Indexed::new(0, document_with_pages(1)),
+            Indexed::new(1, images),
+        ];
+        mirror(&mut documents);
+        let images = documents[1].value();
+        let shifted = contents(images, 1).as_array().unwrap().clone();
+        assert_eq!(shifted.len(), 3);
+        let before = images
+            .get_object(shifted[0].as_reference().unwrap())
+            .unwrap();
+        let operations = Content::decode(&before.as_stream().unwrap().content)
+            .unwrap()
+            .operations;
+        assert_eq!(operations[1].operator, "cm");
+        assert_eq!(operations[1].operands[4].as_float().unwrap(), 30.0);
+        assert!(contents(images, 2).as_reference().is_ok());
+        for page_id in images.get_pages().into_values() {
+            assert!(!images.get_dictionary(page_id).unwrap().has(SHIFT_KEY));
+        }
+    }
+}
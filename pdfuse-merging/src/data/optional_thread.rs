@@ -1,10 +1,10 @@
-use std::{path::PathBuf, thread::JoinHandle};
+use std::{path::PathBuf, sync::Arc, thread::JoinHandle, time::Instant};
 
 use indicatif::{MultiProgress, ProgressBar};
 use pdfuse_parameters::Parameters;
 use pdfuse_utils::{create_temp_dir, get_progress_indicator, Indexed};
 
-use crate::DocumentLoadError;
+use crate::{report::ReportBuilder, DocumentLoadError};
 
 use super::{loaded_document, preload_pdf, Data, PdfResult};
 
@@ -17,6 +17,7 @@ impl ProgressJoinHandle {
         document_paths: Vec<Indexed<PathBuf>>,
         parameters: &Parameters,
         parent_bar: MultiProgress,
+        report: Arc<ReportBuilder>,
     ) -> Self {
         let parameters = parameters.clone();
         let progress_bar = get_progress_indicator(
@@ -25,14 +26,19 @@ impl ProgressJoinHandle {
         );
         parent_bar.add(progress_bar.clone());
         let handle = std::thread::spawn(move || {
-            let paths = convert_data_to_documents(document_paths, &parameters, progress_bar);
+            let paths =
+                convert_data_to_documents(document_paths, &parameters, progress_bar, &report);
             let output: Vec<Indexed<PdfResult<Data>>> = paths
                 .into_iter()
                 .map(|indexed| {
-                    indexed.map_with_index(|result| match result {
+                    let start = Instant::now();
+                    let index = indexed.index();
+                    let loaded = indexed.map_with_index(|result| match result {
                         Ok(path) => preload_pdf(path),
                         Err(err) => Err(err),
-                    })
+                    });
+                    report.record_load(index, start.elapsed());
+                    loaded
                 })
                 .collect();
             output
@@ -56,11 +62,11 @@ pub enum OptionalThread {
 }
 
 impl OptionalThread {
-    pub fn create(document_paths: Vec<Indexed<PathBuf>>, parameters: &Parameters,parent_bar:MultiProgress) -> Self {
+    pub fn create(document_paths: Vec<Indexed<PathBuf>>, parameters: &Parameters,parent_bar:MultiProgress, report: Arc<ReportBuilder>) -> Self {
         if document_paths.is_empty() {
             return Self::NoOp;
         }
-        Self::Thread(ProgressJoinHandle::new(document_paths, parameters, parent_bar))
+        Self::Thread(ProgressJoinHandle::new(document_paths, parameters, parent_bar, report))
     }
     pub fn get_converted_data(self) -> Vec<Indexed<Result<Data, DocumentLoadError>>> {
         match self {
@@ -76,6 +82,7 @@ fn convert_data_to_documents(
     document_paths: Vec<Indexed<PathBuf>>,
     parameters: &Parameters,
     progress_bar: ProgressBar,
+    report: &ReportBuilder,
 ) -> Vec<Indexed<PdfResult<PathBuf>>> {
     if parameters.libreoffice_path.is_none() {
         return vec![];
@@ -85,10 +92,14 @@ fn convert_data_to_documents(
     document_paths
         .into_iter()
         .map(|p| {
-            p.map_with_index(|x| {
+            let start = Instant::now();
+            let index = p.index();
+            let converted = p.map_with_index(|x| {
                 loaded_document::convert_document_to_pdf(&x, &libre_path, &temp_dir)
                     .map_err(Into::into)
-            })
+            });
+            report.record_load(index, start.elapsed());
+            converted
         })
         .inspect(|_| progress_bar.inc(1))
         .collect()
@@ -0,0 +1,392 @@
+use std::collections::HashMap;
+
+use lopdf::{dictionary, Document, Object, ObjectId};
+
+/// Attributes a page can inherit from the `Pages` nodes above it.
+const INHERITABLE_ATTRIBUTES: [&[u8]; 4] = [b"MediaBox", b"CropBox", b"Resources", b"Rotate"];
+/// Largest number of kids of a single node of the page tree.
+const MAX_KIDS: usize = 32;
+/// Key of the trailer of documents made by [`split_pages`], holding the input they were split from and the largest
+/// object id of that input.
+///
+/// Objects up to that id are shared with the other pages of the input, see [`renumber_objects`].
+const SPLIT_SOURCE_KEY: &[u8] = b"PdfuseSplitSource";
+
+/// Copies the attributes the page inherits from its ancestors into the page itself,
+/// so that it keeps them when moved to a different page tree.
+pub(crate) fn materialize_inherited_attributes(document: &mut Document, page_id: ObjectId) {
+    let Ok(page) = document.get_dictionary(page_id) else {
+        return;
+    };
+    let mut inherited: Vec<(&[u8], Object)> = vec![];
+    let mut visited = vec![page_id];
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    while let Some(parent_id) = parent {
+        // malformed files can have cycles in the page tree
+        if visited.contains(&parent_id) {
+            break;
+        }
+        visited.push(parent_id);
+        let Ok(node) = document.get_dictionary(parent_id) else {
+            break;
+        };
+        for key in INHERITABLE_ATTRIBUTES {
+            let known = page.has(key) || inherited.iter().any(|(k, _)| *k == key);
+            if let (false, Ok(value)) = (known, node.get(key)) {
+                inherited.push((key, value.clone()));
+            }
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+    if let Ok(page) = document.get_dictionary_mut(page_id) {
+        for (key, value) in inherited {
+            page.set(key, value);
+        }
+    }
+}
+
+/// Builds a balanced page tree with `root_id` as its root node, with `pages` as leaves in the given order.
+///
+/// Sets `/Parent` of every page and replaces any object with the id of the root.
+pub(crate) fn build_page_tree(document: &mut Document, root_id: ObjectId, pages: &[ObjectId]) {
+    fill_node(document, root_id, None, pages);
+}
+
+fn fill_node(
+    document: &mut Document,
+    node_id: ObjectId,
+    parent: Option<ObjectId>,
+    pages: &[ObjectId],
+) {
+    let kids: Vec<Object> = if pages.len() <= MAX_KIDS {
+        for page_id in pages {
+            if let Ok(page) = document.get_dictionary_mut(*page_id) {
+                page.set("Parent", node_id);
+            }
+        }
+        pages.iter().map(|id| Object::Reference(*id)).collect()
+    } else {
+        // every node gets at most MAX_KIDS kids, some of them may need splitting again
+        let chunk_size = pages.len().div_ceil(MAX_KIDS);
+        pages
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let child_id = document.new_object_id();
+                fill_node(document, child_id, Some(node_id), chunk);
+                Object::Reference(child_id)
+            })
+            .collect()
+    };
+    let mut node = dictionary! {
+        "Type" => "Pages",
+        "Kids" => kids,
+        "Count" => pages.len() as i64,
+    };
+    if let Some(parent_id) = parent {
+        node.set("Parent", parent_id);
+    }
+    document.objects.insert(node_id, node.into());
+}
+
+/// Splits the document of input `source` into single-page documents, in page order.
+///
+/// Each new document holds only the objects used by its page. Objects used by several pages keep their ids, so that
+/// [`renumber_objects`] can merge them back into a single copy.
+pub(crate) fn split_pages(mut document: Document, source: usize) -> Vec<Document> {
+    let pages = document.get_pages();
+    if pages.len() <= 1 {
+        return vec![document];
+    }
+    for page_id in pages.values() {
+        materialize_inherited_attributes(&mut document, *page_id);
+    }
+    pages
+        .into_values()
+        .map(|page_id| {
+            let mut page = extract_page(&document, page_id);
+            page.trailer.set(
+                SPLIT_SOURCE_KEY,
+                vec![(source as i64).into(), (document.max_id as i64).into()],
+            );
+            page
+        })
+        .collect()
+}
+
+/// Gives the objects of the document new ids from `next_id` on, like [`Document::renumber_objects_with`], and returns
+/// the next free id.
+///
+/// Objects a document made by [`split_pages`] shares with the other pages of its input get the same new id for all of
+/// them, kept in `shared` by input and old id, so that they are only copied once into the merged document.
+pub(crate) fn renumber_objects(
+    document: &mut Document,
+    mut next_id: u32,
+    shared: &mut HashMap<(i64, ObjectId), ObjectId>,
+) -> u32 {
+    let split_source = document
+        .trailer
+        .remove(SPLIT_SOURCE_KEY)
+        .and_then(|x| x.as_array().ok().cloned())
+        .and_then(|x| Some((x.first()?.as_i64().ok()?, x.get(1)?.as_i64().ok()?)));
+    let Some((source, source_max_id)) = split_source else {
+        document.renumber_objects_with(next_id);
+        return document.max_id + 1;
+    };
+    let mut new_ids = HashMap::new();
+    for id in document.objects.keys() {
+        let mut fresh_id = || {
+            next_id += 1;
+            (next_id - 1, 0)
+        };
+        // objects added after splitting, like the page tree of the document, are its own
+        let new_id = match i64::from(id.0) <= source_max_id {
+            true => *shared.entry((source, *id)).or_insert_with(fresh_id),
+            false => fresh_id(),
+        };
+        new_ids.insert(*id, new_id);
+    }
+    let objects = std::mem::take(&mut document.objects);
+    document.objects = objects
+        .into_iter()
+        .map(|(id, mut object)| {
+            replace_references(&mut object, &new_ids);
+            (new_ids[&id], object)
+        })
+        .collect();
+    for (_, object) in document.trailer.iter_mut() {
+        replace_references(object, &new_ids);
+    }
+    document.max_id = next_id - 1;
+    next_id
+}
+
+fn replace_references(object: &mut Object, new_ids: &HashMap<ObjectId, ObjectId>) {
+    match object {
+        Object::Reference(id) => {
+            if let Some(new_id) = new_ids.get(id) {
+                *id = *new_id;
+            }
+        }
+        Object::Array(items) => items
+            .iter_mut()
+            .for_each(|x| replace_references(x, new_ids)),
+        Object::Dictionary(dictionary) => dictionary
+            .iter_mut()
+            .for_each(|(_, x)| replace_references(x, new_ids)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter_mut()
+            .for_each(|(_, x)| replace_references(x, new_ids)),
+        _ => (),
+    }
+}
+
+fn extract_page(source: &Document, page_id: ObjectId) -> Document {
+    let mut document = Document::with_version(source.version.clone());
+    let mut to_visit = vec![page_id];
+    while let Some(id) = to_visit.pop() {
+        if document.objects.contains_key(&id) {
+            continue;
+        }
+        let Ok(object) = source.get_object(id) else {
+            continue;
+        };
+        // do not drag other pages (e.g. link destinations) and the old page tree along
+        if id != page_id && matches!(object.type_name(), Ok(b"Page" | b"Pages")) {
+            continue;
+        }
+        let mut object = object.clone();
+        if id == page_id {
+            if let Ok(page) = object.as_dict_mut() {
+                page.remove(b"Parent");
+            }
+        }
+        collect_references(&object, &mut to_visit);
+        document.objects.insert(id, object);
+    }
+    document.max_id = source.max_id;
+    let pages_id = document.new_object_id();
+    if let Ok(page) = document.get_dictionary_mut(page_id) {
+        page.set("Parent", pages_id);
+    }
+    document.objects.insert(
+        pages_id,
+        dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }
+        .into(),
+    );
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    document.trailer.set("Root", catalog_id);
+    document
+}
+
+pub(crate) fn collect_references(object: &Object, references: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => references.push(*id),
+        Object::Array(items) => items.iter().for_each(|x| collect_references(x, references)),
+        Object::Dictionary(dictionary) => dictionary
+            .iter()
+            .for_each(|(_, x)| collect_references(x, references)),
+        Object::Stream(stream) => stream
+            .dict
+            .iter()
+            .for_each(|(_, x)| collect_references(x, references)),
+        _ => (),
+    }
+}
+
+/// Names and objects of the XObjects in the resources of every page.
+pub(crate) fn page_xobjects(document: &Document) -> Vec<(String, ObjectId)> {
+    let mut xobjects = vec![];
+    for page_id in document.get_pages().into_values() {
+        let Ok((resources, resource_ids)) = document.get_page_resources(page_id) else {
+            continue;
+        };
+        let dictionaries = resources.into_iter().chain(
+            resource_ids
+                .iter()
+                .filter_map(|id| document.get_dictionary(*id).ok()),
+        );
+        for dictionary in dictionaries {
+            let named = match dictionary.get(b"XObject") {
+                Ok(Object::Dictionary(named)) => named,
+                Ok(Object::Reference(id)) => match document.get_dictionary(*id) {
+                    Ok(named) => named,
+                    Err(_) => continue,
+                },
+                _ => continue,
+            };
+            for (name, object) in named.iter() {
+                if let Ok(id) = object.as_reference() {
+                    xobjects.push((String::from_utf8_lossy(name).into_owned(), id));
+                }
+            }
+        }
+    }
+    xobjects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_documents;
+
+    /// A document with the given number of pages, along with their ids in order.
+    fn document_with_pages(count: usize) -> (Document, Vec<ObjectId>) {
+        let document = test_documents::document_with_pages(count);
+        let pages = document.get_pages().into_values().collect();
+        (document, pages)
+    }
+
+    fn max_kids(document: &Document, node_id: ObjectId) -> usize {
+        let kids = document
+            .get_dictionary(node_id)
+            .and_then(|node| node.get(b"Kids"))
+            .and_then(Object::as_array)
+            .map(|kids| kids.iter().flat_map(Object::as_reference).collect::<Vec<_>>())
+            .unwrap_or_default();
+        kids.iter()
+            .map(|kid| max_kids(document, *kid))
+            .fold(kids.len(), usize::max)
+    }
+
+    #[test]
+    fn small_tree_is_flat() {
+        let (mut document, pages) = document_with_pages(5);
+        let root_id = document.new_object_id();
+        build_page_tree(&mut document, root_id, &pages);
+        let kids = document
+            .get_dictionary(root_id)
+            .and_then(|root| root.get(b"Kids"))
+            .and_then(Object::as_array)
+            .unwrap();
+        assert_eq!(kids.len(), 5);
+    }
+
+    #[test]
+    fn large_tree_is_balanced_and_ordered() {
+        let (mut document, pages) = document_with_pages(5000);
+        let root_id = document.new_object_id();
+        build_page_tree(&mut document, root_id, &pages);
+        let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => root_id });
+        document.trailer.set("Root", catalog_id);
+
+        assert!(max_kids(&document, root_id) <= MAX_KIDS);
+        let found: Vec<ObjectId> = document.get_pages().into_values().collect();
+        assert_eq!(found, pages);
+    }
+
+    #[test]
+    fn split_pages_share_objects() {
+        let (mut document, pages) = document_with_pages(3);
+        let font_id = document.add_object(dictionary! { "Type" => "Font" });
+        for page_id in &pages {
+            let page = document.get_dictionary_mut(*page_id).unwrap();
+            page.set(
+                "Resources",
+                dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            );
+        }
+        let root_id = document.new_object_id();
+        build_page_tree(&mut document, root_id, &pages);
+        let catalog_id =
+            document.add_object(dictionary! { "Type" => "Catalog", "Pages" => root_id });
+        document.trailer.set("Root", catalog_id);
+
+        let mut shared = HashMap::new();
+        let mut next_id = 1;
+        let mut page_ids = vec![];
+        let mut font_ids = vec![];
+        for mut page in split_pages(document, 0) {
+            next_id = renumber_objects(&mut page, next_id, &mut shared);
+            let page_id = *page.get_pages().values().next().unwrap();
+            let font_id = page
+                .get_dictionary(page_id)
+                .and_then(|page| page.get(b"Resources"))
+                .and_then(Object::as_dict)
+                .and_then(|resources| resources.get(b"Font"))
+                .and_then(Object::as_dict)
+                .and_then(|fonts| fonts.get(b"F1"))
+                .and_then(Object::as_reference)
+                .unwrap();
+            assert!(page.get_object(font_id).is_ok());
+            page_ids.push(page_id);
+            font_ids.push(font_id);
+        }
+        font_ids.dedup();
+        assert_eq!(font_ids.len(), 1);
+        page_ids.dedup();
+        assert_eq!(page_ids.len(), 3);
+    }
+
+    #[test]
+    fn inherited_attributes_are_copied() {
+        let mut document = Document::with_version("1.5");
+        let root_id = document.add_object(dictionary! {
+            "Type" => "Pages",
+            "MediaBox" => vec![0.into(), 0.into(), 300.into(), 300.into()],
+            "Rotate" => 90,
+        });
+        let node_id = document.add_object(dictionary! {
+            "Type" => "Pages",
+            "Parent" => root_id,
+            "Rotate" => 180,
+        });
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => node_id,
+        });
+        materialize_inherited_attributes(&mut document, page_id);
+
+        let page = document.get_dictionary(page_id).unwrap();
+        assert_eq!(page.get(b"Rotate").and_then(Object::as_i64).unwrap(), 180);
+        assert_eq!(page.get(b"MediaBox").and_then(Object::as_array).unwrap().len(), 4);
+        assert!(!page.has(b"Resources"));
+    }
+}
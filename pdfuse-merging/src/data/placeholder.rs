@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+
+use lopdf::{Document, Object, ObjectId, Stream};
+use printpdf::{RawImage, RawImageData, RawImageFormat};
+
+use super::page_tree;
+
+/// One pixel image, added to the printpdf document in place of an image XObject that printpdf cannot create,
+/// to be replaced by [`replace`].
+///
+/// Drawn at 72 DPI, so its scale is the size of the image in points.
+pub(crate) fn image() -> RawImage {
+    RawImage {
+        width: 1,
+        height: 1,
+        data_format: RawImageFormat::R8,
+        pixels: RawImageData::U8(vec![0]),
+        tag: vec![],
+    }
+}
+
+/// Replaces the placeholder images named with the keys of `streams` with the streams.
+pub(crate) fn replace(document: &mut Document, mut streams: HashMap<String, Stream>) {
+    if streams.is_empty() {
+        return;
+    }
+    let targets: Vec<(ObjectId, Stream)> = page_tree::page_xobjects(document)
+        .into_iter()
+        .filter_map(|(name, id)| Some((id, streams.remove(&name)?)))
+        .collect();
+    for (id, stream) in targets {
+        document.objects.insert(id, Object::Stream(stream));
+    }
+}
@@ -0,0 +1,163 @@
+use std::ops::RangeInclusive;
+
+use image::{metadata::Orientation, ImageFormat};
+
+/// Resolutions outside of this range are placeholders written by some programs rather than real measurements.
+const PLAUSIBLE_DPI: RangeInclusive<f64> = 50.0..=10_000.0;
+const CENTIMETERS_PER_INCH: f64 = 2.54;
+pub(crate) const METERS_PER_INCH: f64 = 0.0254;
+
+/// Horizontal and vertical resolution in dots per inch.
+pub type Resolution = (f64, f64);
+
+/// Reads the resolution saved in the file: JFIF density of JPEGs and `pHYs` of PNGs.
+///
+/// TIFFs have a resolution for every page, read along with the page.
+///
+/// Returns `None` if the file has no physical resolution or it is not plausible.
+pub(crate) fn read(bytes: &[u8], format: Option<ImageFormat>) -> Option<Resolution> {
+    match format? {
+        ImageFormat::Jpeg => jfif(bytes),
+        ImageFormat::Png => png(bytes),
+        _ => None,
+    }
+    .and_then(plausible)
+}
+
+/// Swaps the directions of the resolution if the orientation turned the image on its side.
+pub(crate) fn turned(resolution: Resolution, orientation: Option<Orientation>) -> Resolution {
+    match orientation {
+        Some(
+            Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH,
+        ) => (resolution.1, resolution.0),
+        _ => resolution,
+    }
+}
+
+/// Keeps the resolution only if both directions are plausible.
+pub(crate) fn plausible(resolution: Resolution) -> Option<Resolution> {
+    let (horizontal, vertical) = resolution;
+    (PLAUSIBLE_DPI.contains(&horizontal) && PLAUSIBLE_DPI.contains(&vertical)).then_some(resolution)
+}
+
+/// Converts a resolution given per inch or per centimeter to dots per inch.
+pub(crate) fn per_inch(horizontal: f64, vertical: f64, per_centimeter: bool) -> Resolution {
+    match per_centimeter {
+        true => (
+            horizontal * CENTIMETERS_PER_INCH,
+            vertical * CENTIMETERS_PER_INCH,
+        ),
+        false => (horizontal, vertical),
+    }
+}
+
+/// Density from the JFIF segment, which has to follow the start of the image.
+fn jfif(bytes: &[u8]) -> Option<Resolution> {
+    if bytes.get(0..4)? != [0xFF, 0xD8, 0xFF, 0xE0] || bytes.get(6..11)? != b"JFIF\0" {
+        return None;
+    }
+    // version, density unit, horizontal and vertical density
+    let [_, _, unit, x1, x0, y1, y0] = *bytes.get(11..18)? else {
+        return None;
+    };
+    let horizontal = u16::from_be_bytes([x1, x0]) as f64;
+    let vertical = u16::from_be_bytes([y1, y0]) as f64;
+    match unit {
+        1 => Some(per_inch(horizontal, vertical, false)),
+        2 => Some(per_inch(horizontal, vertical, true)),
+        // only the aspect ratio of pixels
+        _ => None,
+    }
+}
+
+/// Pixels per meter from the `pHYs` chunk, which has to come before the image data.
+fn png(bytes: &[u8]) -> Option<Resolution> {
+    if bytes.get(0..8)? != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let mut position = 8;
+    loop {
+        let length =
+            u32::from_be_bytes(bytes.get(position..position + 4)?.try_into().ok()?) as usize;
+        let kind = bytes.get(position + 4..position + 8)?;
+        let data = bytes.get(position + 8..(position + 8).checked_add(length)?)?;
+        match kind {
+            b"pHYs" => {
+                let [x3, x2, x1, x0, y3, y2, y1, y0, unit] = *data else {
+                    return None;
+                };
+                let horizontal = u32::from_be_bytes([x3, x2, x1, x0]) as f64;
+                let vertical = u32::from_be_bytes([y3, y2, y1, y0]) as f64;
+                // unit 0 gives only the aspect ratio of pixels
+                return (unit == 1)
+                    .then_some((horizontal * METERS_PER_INCH, vertical * METERS_PER_INCH));
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => position += length + 12,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{codecs::jpeg::JpegEncoder, ExtendedColorType, ImageEncoder};
+
+    use super::*;
+
+    fn jpeg(unit: u8, horizontal: u16, vertical: u16) -> Vec<u8> {
+        let mut bytes = Cursor::new(vec![]);
+        JpegEncoder::new(&mut bytes)
+            .write_image(&[0; 4], 2, 2, ExtendedColorType::L8)
+            .unwrap();
+        let mut bytes = bytes.into_inner();
+        bytes[13] = unit;
+        bytes[14..16].copy_from_slice(&horizontal.to_be_bytes());
+        bytes[16..18].copy_from_slice(&vertical.to_be_bytes());
+        bytes
+    }
+
+    fn png(physical: Option<(u32, u32, u8)>) -> Vec<u8> {
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        let mut chunk = |kind: &[u8], data: &[u8]| {
+            bytes.extend((data.len() as u32).to_be_bytes());
+            bytes.extend(kind);
+            bytes.extend(data);
+            // the checksum is not verified
+            bytes.extend([0; 4]);
+        };
+        chunk(b"IHDR", &[0; 13]);
+        if let Some((horizontal, vertical, unit)) = physical {
+            let mut data = horizontal.to_be_bytes().to_vec();
+            data.extend(vertical.to_be_bytes());
+            data.push(unit);
+            chunk(b"pHYs", &data);
+        }
+        chunk(b"IDAT", &[]);
+        bytes
+    }
+
+    #[test]
+    fn reads_jfif_density() {
+        let format = Some(ImageFormat::Jpeg);
+        assert_eq!(read(&jpeg(1, 300, 600), format), Some((300.0, 600.0)));
+        let (horizontal, vertical) = read(&jpeg(2, 118, 118), format).unwrap();
+        assert_eq!((horizontal.round(), vertical.round()), (300.0, 300.0));
+        assert_eq!(read(&jpeg(0, 1, 1), format), None);
+        assert_eq!(read(&jpeg(1, 1, 1), format), None);
+    }
+
+    #[test]
+    fn reads_png_physical_size() {
+        let format = Some(ImageFormat::Png);
+        let (horizontal, vertical) = read(&png(Some((11811, 7874, 1))), format).unwrap();
+        assert_eq!((horizontal.round(), vertical.round()), (300.0, 200.0));
+        assert_eq!(read(&png(Some((1, 1, 0))), format), None);
+        assert_eq!(read(&png(None), format), None);
+        assert_eq!(read(b"\x89PNG\r\n\x1a\n", format), None);
+    }
+}
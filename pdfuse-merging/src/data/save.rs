@@ -0,0 +1,297 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::File,
+    io::{self, BufWriter, Write},
+};
+
+use lopdf::{
+    content::{Content, Operation},
+    dictionary,
+    xref::XrefType,
+    Dictionary, Document, Object, ObjectId, Stream,
+};
+use pdfuse_parameters::{Parameters, PdfVersion};
+use pdfuse_utils::{info_t, warn_t};
+
+use super::page_tree::collect_references;
+use crate::report::PrunedObjects;
+
+/// Largest number of objects packed into a single object stream.
+const OBJECTS_PER_STREAM: usize = 100;
+
+/// Saves the document with the PDF version and layout selected in the parameters.
+pub(crate) fn save_document(
+    document: &mut Document,
+    output_path: &str,
+    parameters: &Parameters,
+) -> io::Result<()> {
+    let mut version = parameters.pdf_version;
+    // `/JPXDecode` was introduced in PDF 1.5
+    if version < PdfVersion::V1_5 && has_jpeg2000(document) {
+        warn_t!("warning.jpeg2000_version", version = version.as_str());
+        version = PdfVersion::V1_5;
+    }
+    document.version = version.as_str().to_owned();
+    // cross-reference streams were introduced in PDF 1.5
+    let object_streams = parameters.object_streams && version >= PdfVersion::V1_5;
+    if parameters.object_streams && !object_streams {
+        warn_t!("warning.object_streams_version", version = version.as_str());
+    }
+    // without object streams the cross-reference table is kept, whatever the version
+    if !object_streams {
+        document.reference_table.cross_reference_type = XrefType::CrossReferenceTable;
+        return document.save(output_path).map(|_| ());
+    }
+    let mut writer = PdfWriter::new(BufWriter::new(File::create(output_path)?));
+    writer.write_with_object_streams(document)?;
+    writer.inner.flush()
+}
+
+/// Checks if any image of the document is compressed as JPEG 2000.
+fn has_jpeg2000(document: &Document) -> bool {
+    document.objects.values().any(|object| {
+        object.as_stream().is_ok_and(|stream| {
+            stream
+                .filters()
+                .is_ok_and(|filters| filters.contains(&&b"JPXDecode"[..]))
+        })
+    })
+}
+
+/// Removes objects which cannot be reached from the trailer.
+pub(crate) fn prune_unreferenced(document: &mut Document) -> PrunedObjects {
+    let mut reachable: HashSet<ObjectId> = HashSet::new();
+    let mut to_visit = vec![];
+    for (_, value) in document.trailer.iter() {
+        collect_references(value, &mut to_visit);
+    }
+    while let Some(id) = to_visit.pop() {
+        if !reachable.insert(id) {
+            continue;
+        }
+        if let Some(object) = document.objects.get(&id) {
+            collect_references(object, &mut to_visit);
+        }
+    }
+    let orphans: Vec<ObjectId> = document
+        .objects
+        .keys()
+        .filter(|id| !reachable.contains(id))
+        .copied()
+        .collect();
+    let bytes = orphans
+        .iter()
+        .filter_map(|id| document.objects.remove(id))
+        .map(|object| serialized_size(&object))
+        .sum();
+    let pruned = PrunedObjects {
+        objects: orphans.len(),
+        bytes,
+    };
+    if pruned.objects > 0 {
+        info_t!("info.pruned_objects", count = pruned.objects, bytes = pruned.bytes);
+    }
+    pruned
+}
+
+fn serialized_size(object: &Object) -> u64 {
+    let size = match object {
+        Object::Stream(stream) => {
+            serialize(&Object::Dictionary(stream.dict.clone())).map_or(0, |x| x.len())
+                + stream.content.len()
+        }
+        _ => serialize(object).map_or(0, |x| x.len()),
+    };
+    size as u64
+}
+
+enum XrefEntry {
+    Direct { offset: u64, generation: u16 },
+    Compressed { stream_id: u32, index: u16 },
+}
+
+/// Writes documents with non-stream objects packed into object streams.
+///
+/// Needed because the writer of lopdf does not create object streams.
+struct PdfWriter<W: Write> {
+    inner: W,
+    position: u64,
+    xref: BTreeMap<u32, XrefEntry>,
+}
+
+impl<W: Write> PdfWriter<W> {
+    fn new(inner: W) -> Self {
+        PdfWriter {
+            inner,
+            position: 0,
+            xref: BTreeMap::new(),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.inner.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_with_object_streams(&mut self, document: &Document) -> io::Result<()> {
+        self.write(format!("%PDF-{}\n", document.version).as_bytes())?;
+        // binary mark, tells transfer tools not to treat the file as text
+        self.write(b"%\xe2\xe3\xcf\xd3\n")?;
+        let mut next_id = document.objects.keys().map(|(id, _)| *id).max().unwrap_or(0) + 1;
+
+        // streams cannot be stored in object streams, neither can objects with a non-zero generation
+        let (packable, direct): (Vec<_>, Vec<_>) = document
+            .objects
+            .iter()
+            .partition(|((_, generation), object)| {
+                *generation == 0 && !matches!(object, Object::Stream(_))
+            });
+        for ((id, generation), object) in direct {
+            self.write_indirect_object(*id, *generation, object)?;
+        }
+        for chunk in packable.chunks(OBJECTS_PER_STREAM) {
+            let stream_id = next_id;
+            next_id += 1;
+            let mut offsets = Vec::with_capacity(chunk.len());
+            let mut body = Vec::new();
+            for (index, ((id, _), object)) in chunk.iter().enumerate() {
+                offsets.push(format!("{id} {}", body.len()));
+                body.extend(serialize(object)?);
+                body.push(b'\n');
+                self.xref.insert(
+                    *id,
+                    XrefEntry::Compressed {
+                        stream_id,
+                        index: index as u16,
+                    },
+                );
+            }
+            let mut content = offsets.join(" ").into_bytes();
+            content.push(b'\n');
+            let first = content.len();
+            content.extend(body);
+            let mut stream = Stream::new(
+                dictionary! {
+                    "Type" => "ObjStm",
+                    "N" => chunk.len() as i64,
+                    "First" => first as i64,
+                },
+                content,
+            );
+            stream.compress().map_err(io::Error::other)?;
+            self.write_indirect_object(stream_id, 0, &Object::Stream(stream))?;
+        }
+        self.write_xref_stream(&document.trailer, next_id)
+    }
+
+    fn write_indirect_object(&mut self, id: u32, generation: u16, object: &Object) -> io::Result<()> {
+        self.xref.insert(
+            id,
+            XrefEntry::Direct {
+                offset: self.position,
+                generation,
+            },
+        );
+        self.write(format!("{id} {generation} obj\n").as_bytes())?;
+        match object {
+            Object::Stream(stream) => {
+                self.write(&serialize(&Object::Dictionary(stream.dict.clone()))?)?;
+                self.write(b"stream\n")?;
+                self.write(&stream.content)?;
+                self.write(b"\nendstream")?;
+            }
+            _ => self.write(&serialize(object)?)?,
+        }
+        self.write(b"\nendobj\n")
+    }
+
+    /// Writes the cross-reference stream, which also replaces the trailer.
+    fn write_xref_stream(&mut self, trailer: &Dictionary, xref_id: u32) -> io::Result<()> {
+        let start = self.position;
+        self.xref.insert(
+            xref_id,
+            XrefEntry::Direct {
+                offset: start,
+                generation: 0,
+            },
+        );
+        let offset_width: usize = if start > u32::MAX as u64 { 8 } else { 4 };
+        let mut content = Vec::with_capacity((xref_id as usize + 1) * (offset_width + 3));
+        for id in 0..=xref_id {
+            let (kind, field, generation): (u8, u64, u16) = match self.xref.get(&id) {
+                Some(XrefEntry::Direct { offset, generation }) => (1, *offset, *generation),
+                Some(XrefEntry::Compressed { stream_id, index }) => (2, *stream_id as u64, *index),
+                None if id == 0 => (0, 0, u16::MAX),
+                None => (0, 0, 0),
+            };
+            content.push(kind);
+            content.extend(&field.to_be_bytes()[8 - offset_width..]);
+            content.extend(generation.to_be_bytes());
+        }
+        let mut dict = dictionary! {
+            "Type" => "XRef",
+            "Size" => xref_id as i64 + 1,
+            "W" => vec![1.into(), (offset_width as i64).into(), 2.into()],
+        };
+        for key in [b"Root".as_slice(), b"Info", b"ID"] {
+            if let Ok(value) = trailer.get(key) {
+                dict.set(key, value.clone());
+            }
+        }
+        let mut stream = Stream::new(dict, content);
+        stream.compress().map_err(io::Error::other)?;
+        self.write_indirect_object(xref_id, 0, &Object::Stream(stream))?;
+        self.write(format!("startxref\n{start}\n%%EOF\n").as_bytes())
+    }
+}
+
+/// Serializes a single object in PDF syntax.
+fn serialize(object: &Object) -> io::Result<Vec<u8>> {
+    // the object writer of lopdf is private, content encoding is the only public way to reach it
+    let content = Content {
+        operations: vec![Operation::new("", vec![object.clone()])],
+    };
+    let mut bytes = content.encode().map_err(io::Error::other)?;
+    // separator written before the (empty) operator
+    bytes.pop();
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::test_documents::document_with_pages;
+
+    #[test]
+    fn prune_removes_only_orphans() {
+        let mut document = document_with_pages(1);
+        let count = document.objects.len();
+        let orphan_id = document.add_object(dictionary! { "Type" => "Outlines" });
+        let pruned = prune_unreferenced(&mut document);
+
+        assert_eq!(pruned.objects, 1);
+        assert!(pruned.bytes > 0);
+        assert_eq!(document.objects.len(), count);
+        assert!(!document.objects.contains_key(&orphan_id));
+    }
+
+    #[test]
+    fn serialize_dictionary() {
+        let object: Object = dictionary! { "Type" => "Page", "Count" => 1 }.into();
+        assert_eq!(serialize(&object).unwrap(), b"<</Type/Page/Count 1>>");
+    }
+
+    #[test]
+    fn object_streams_can_be_read_back() {
+        let document = document_with_pages(1);
+        let mut writer = PdfWriter::new(Vec::new());
+        writer.write_with_object_streams(&document).unwrap();
+
+        let loaded = Document::load_mem(&writer.inner).unwrap();
+        let pages = loaded.get_pages();
+        assert_eq!(pages.len(), 1);
+        let content = loaded.get_page_content(pages[&1]).unwrap();
+        assert_eq!(content, b"0 0 10 10 re f");
+    }
+}
@@ -0,0 +1,244 @@
+use image::{
+    imageops::{self, FilterType},
+    DynamicImage, GrayImage, ImageBuffer, Pixel, RgbImage,
+};
+
+use super::{bilevel, LoadedImage};
+use crate::error::ImageLoadError;
+
+/// Largest tilt looked for, in degrees.
+const MAX_SKEW: f64 = 10.0;
+const COARSE_STEP: f64 = 0.5;
+const FINE_STEP: f64 = 0.05;
+/// Longer side of the copy of the image searched for the tilt.
+const SEARCH_SIZE: u32 = 1000;
+/// Part of each edge left out of the search, where the scanner bed may show.
+const IGNORED_EDGE: f32 = 0.05;
+/// Part of the pixels of a border line which may differ from its color, like dust.
+const BORDER_NOISE: f64 = 0.01;
+
+/// Straightens a tilted scan and cuts off the uniform borders around it, in that order.
+///
+/// `auto_crop` is the largest difference of a channel from the color of a border that still belongs to it.
+pub(crate) fn clean_up(
+    image: &mut LoadedImage,
+    deskew: bool,
+    auto_crop: Option<u8>,
+) -> Result<(), ImageLoadError> {
+    if deskew {
+        if let Some(angle) = skew_angle(&image.visible()?.to_luma8()) {
+            image.straighten(angle)?;
+        }
+    }
+    if let Some(tolerance) = auto_crop {
+        if let Some((x, y, width, height)) = content_bounds(&image.visible()?.to_rgb8(), tolerance) {
+            image.crop(x, y, width, height)?;
+        }
+    }
+    Ok(())
+}
+
+/// Angle in degrees by which the lines of the image go down to the right, if they are tilted.
+///
+/// Dark pixels are projected along each candidate angle; the lines of text (or the edges of the page)
+/// pile up in the fewest rows at the angle they are tilted by.
+fn skew_angle(image: &GrayImage) -> Option<f64> {
+    let scale = SEARCH_SIZE as f32 / image.width().max(image.height()) as f32;
+    let small = match scale < 1.0 {
+        true => imageops::resize(
+            image,
+            (image.width() as f32 * scale).max(1.0) as u32,
+            (image.height() as f32 * scale).max(1.0) as u32,
+            FilterType::Triangle,
+        ),
+        false => image.clone(),
+    };
+    let (width, height) = small.dimensions();
+    let threshold = bilevel::otsu_threshold(&small);
+    let (x_edge, y_edge) = (
+        (width as f32 * IGNORED_EDGE) as u32,
+        (height as f32 * IGNORED_EDGE) as u32,
+    );
+    let dark: Vec<(f64, f64)> = small
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| {
+            (x_edge..width - x_edge).contains(x)
+                && (y_edge..height - y_edge).contains(y)
+                && pixel.0[0] < threshold
+        })
+        .map(|(x, y, _)| (x as f64, y as f64))
+        .collect();
+    // nothing to line up, or a photo rather than a document
+    if dark.is_empty() || dark.len() > (width * height / 2) as usize {
+        return None;
+    }
+    let best = |angles: Vec<f64>| {
+        angles
+            .into_iter()
+            .map(|angle| (angle, sharpness(&dark, angle, height)))
+            // of equally sharp angles, the one closest to level
+            .max_by(|a, b| a.1.total_cmp(&b.1).then(b.0.abs().total_cmp(&a.0.abs())))
+            .map(|(angle, _)| angle)
+    };
+    let steps = |center: f64, range: f64, step: f64| {
+        let count = (range / step).round() as i32;
+        (-count..=count)
+            .map(|i| center + i as f64 * step)
+            .collect::<Vec<_>>()
+    };
+    let coarse = best(steps(0.0, MAX_SKEW, COARSE_STEP))?;
+    let fine = best(steps(coarse, COARSE_STEP, FINE_STEP))?;
+    (fine.abs() >= FINE_STEP).then_some(fine)
+}
+
+/// Sum of squared row counts of the points projected along the angle, higher when they form lines.
+fn sharpness(points: &[(f64, f64)], angle: f64, height: u32) -> f64 {
+    let slope = angle.to_radians().tan();
+    // points move by at most `x * slope`, which is less than the width
+    let offset = SEARCH_SIZE as usize;
+    let mut rows = vec![0u32; height as usize + 2 * offset];
+    for (x, y) in points {
+        let row = (y - x * slope).round() as isize + offset as isize;
+        if let Some(count) = rows.get_mut(row.max(0) as usize) {
+            *count += 1;
+        }
+    }
+    rows.iter().map(|&count| (count as f64).powi(2)).sum()
+}
+
+/// The image turned around its center so that lines tilted by `angle` degrees become level.
+///
+/// The size stays the same; corners brought in from outside repeat the nearest edge of the image.
+pub(crate) fn rotated(image: &DynamicImage, angle: f64) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(buffer) => rotated_buffer(buffer, angle).into(),
+        DynamicImage::ImageLumaA8(buffer) => rotated_buffer(buffer, angle).into(),
+        DynamicImage::ImageRgb8(buffer) => rotated_buffer(buffer, angle).into(),
+        DynamicImage::ImageRgba8(buffer) => rotated_buffer(buffer, angle).into(),
+        DynamicImage::ImageLuma16(buffer) => rotated_buffer(buffer, angle).into(),
+        DynamicImage::ImageLumaA16(buffer) => rotated_buffer(buffer, angle).into(),
+        DynamicImage::ImageRgb16(buffer) => rotated_buffer(buffer, angle).into(),
+        DynamicImage::ImageRgba16(buffer) => rotated_buffer(buffer, angle).into(),
+        other => rotated_buffer(&other.to_rgba32f(), angle).into(),
+    }
+}
+
+fn rotated_buffer<P: Pixel>(
+    image: &ImageBuffer<P, Vec<P::Subpixel>>,
+    angle: f64,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    let (width, height) = image.dimensions();
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (center_x, center_y) = ((width as f64 - 1.0) / 2.0, (height as f64 - 1.0) / 2.0);
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (dx, dy) = (x as f64 - center_x, y as f64 - center_y);
+        let source_x = (center_x + dx * cos - dy * sin).clamp(0.0, width as f64 - 1.0);
+        let source_y = (center_y + dx * sin + dy * cos).clamp(0.0, height as f64 - 1.0);
+        imageops::interpolate_bilinear(image, source_x as f32, source_y as f32)
+            .unwrap_or_else(|| *image.get_pixel(x, y))
+    })
+}
+
+/// Rectangle left after removing lines from each edge which have the color of the outermost line,
+/// as `(x, y, width, height)`.
+///
+/// Returns `None` if there are no such lines, or if the whole image is uniform.
+fn content_bounds(image: &RgbImage, tolerance: u8) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let row = |y: u32| (0..width).map(move |x| image.get_pixel(x, y).0);
+    let column = |x: u32| (0..height).map(move |y| image.get_pixel(x, y).0);
+    let uniform_lines = |lines: &mut dyn Iterator<Item = Vec<[u8; 3]>>| -> u32 {
+        let mut lines = lines.peekable();
+        let Some(border) = lines.peek().map(|line| average(line)) else {
+            return 0;
+        };
+        lines
+            .take_while(|line| {
+                let noise = line
+                    .iter()
+                    .filter(|pixel| {
+                        pixel
+                            .iter()
+                            .zip(border)
+                            .any(|(a, b)| a.abs_diff(b) > tolerance)
+                    })
+                    .count();
+                noise as f64 <= line.len() as f64 * BORDER_NOISE
+            })
+            .count() as u32
+    };
+    let top = uniform_lines(&mut (0..height).map(|y| row(y).collect()));
+    if top == height {
+        return None;
+    }
+    let bottom = uniform_lines(&mut (0..height).rev().map(|y| row(y).collect()));
+    let left = uniform_lines(&mut (0..width).map(|x| column(x).collect()));
+    let right = uniform_lines(&mut (0..width).rev().map(|x| column(x).collect()));
+    let (cropped_width, cropped_height) = (
+        width.checked_sub(left + right)?,
+        height.checked_sub(top + bottom)?,
+    );
+    let cropped = (left, top, cropped_width, cropped_height);
+    (cropped_width > 0 && cropped_height > 0 && cropped != (0, 0, width, height)).then_some(cropped)
+}
+
+fn average(line: &[[u8; 3]]) -> [u8; 3] {
+    let mut sums = [0u64; 3];
+    for pixel in line {
+        for (sum, channel) in sums.iter_mut().zip(pixel) {
+            *sum += *channel as u64;
+        }
+    }
+    sums.map(|sum| (sum / line.len().max(1) as u64) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Luma, Rgb};
+
+    use super::*;
+
+    /// White page with black lines going down to the right by `angle` degrees.
+    fn lines(angle: f64) -> GrayImage {
+        let slope = angle.to_radians().tan();
+        GrayImage::from_fn(400, 300, |x, y| {
+            let row = y as f64 - x as f64 * slope;
+            Luma([if row.rem_euclid(30.0) < 3.0 { 0 } else { 255 }])
+        })
+    }
+
+    #[test]
+    fn finds_skew() {
+        let angle = skew_angle(&lines(2.0)).unwrap();
+        assert!((angle - 2.0).abs() < 0.2, "{angle}");
+        let angle = skew_angle(&lines(-3.5)).unwrap();
+        assert!((angle + 3.5).abs() < 0.2, "{angle}");
+        assert_eq!(skew_angle(&lines(0.0)), None);
+        assert_eq!(
+            skew_angle(&GrayImage::from_pixel(50, 50, Luma([255]))),
+            None
+        );
+    }
+
+    #[test]
+    fn straightens_lines() {
+        let straightened = rotated(&DynamicImage::ImageLuma8(lines(2.0)), 2.0).to_luma8();
+        assert_eq!(skew_angle(&straightened), None);
+    }
+
+    #[test]
+    fn finds_content_inside_borders() {
+        let image = RgbImage::from_fn(100, 80, |x, y| match (x, y) {
+            (10..90, 5..70) => Rgb([250, 250, 250]),
+            // a speck of dust on the border
+            (50, 2) => Rgb([0, 0, 0]),
+            _ => Rgb([40, 42, 38]),
+        });
+        assert_eq!(content_bounds(&image, 16), Some((10, 5, 80, 65)));
+        let plain = RgbImage::from_pixel(20, 20, Rgb([255, 255, 255]));
+        assert_eq!(content_bounds(&plain, 16), None);
+    }
+}
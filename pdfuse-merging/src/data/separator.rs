@@ -0,0 +1,60 @@
+use std::{fs, path::Path};
+
+use chrono::{DateTime, Local};
+use image::DynamicImage;
+use lopdf::Document;
+use pdfuse_parameters::{Parameters, SourcePath};
+use pdfuse_utils::debug_t;
+use rust_i18n::t;
+
+use super::{loaded_document::media_box_size, text_page::TextPage, LoadedImage};
+
+/// Creates a page describing `source`, to be inserted before its pages.
+///
+/// The page has the size of the first page of `document`, so that it blends in with the file it describes.
+pub(crate) fn separator_document(
+    source: &SourcePath,
+    document: &Document,
+    parameters: &Parameters,
+) -> Document {
+    let page_size = document
+        .get_pages()
+        .values()
+        .next()
+        .and_then(|page| media_box_size(document, *page))
+        .unwrap_or_else(|| parameters.image_page_fallback_size.into());
+    let path: &Path = source.as_ref();
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| source.to_string());
+
+    let mut page = TextPage::new(page_size);
+    page.add_line(file_name)
+        .add_line(source.to_string())
+        .add_line("")
+        .add_line(t!("separator.page_count", count = document.get_pages().len()));
+    if let Some(modified) = modification_date(path) {
+        page.add_line(t!(
+            "separator.modified",
+            date = modified.format("%Y-%m-%d %H:%M:%S")
+        ));
+    }
+    if parameters.separator_thumbnails && matches!(source, SourcePath::Image(_)) {
+        let thumbnail = LoadedImage::load(path, parameters)
+            .and_then(|image| Ok(DynamicImage::try_from(image)?));
+        match thumbnail {
+            Ok(thumbnail) => {
+                page.set_thumbnail(thumbnail);
+            }
+            Err(err) => debug_t!("debug.thumbnail_failed", path = source, error = err),
+        }
+    }
+    page.into_document()
+}
+
+/// Time the file was last modified.
+pub(crate) fn modification_date(path: &Path) -> Option<DateTime<Local>> {
+    let modified = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+    Some(modified.into())
+}
@@ -1,5 +1,5 @@
 
-use pdfuse_parameters::{Parameters, SourcePath};
+use pdfuse_parameters::{ImagePageSizing, Parameters, SourcePath};
 use pdfuse_sizing::{CustomSize, Size};
 use pdfuse_utils::Indexed;
 
@@ -10,7 +10,7 @@ pub(crate) enum GuideRequirement {
     /// Two cases:
     /// <ul>
     ///     <li>There are only images and/or PDFs.</li>
-    ///     <li>Fallback size is forced.</li>
+    ///     <li>Fallback size is forced, or pages match images.</li>
     /// </ul>
     /// PDFs are ignored, since it easy to get their size.
     SizeInformationNotNeeded,
@@ -33,7 +33,9 @@ impl SizeGuide {
         source_paths: &[Indexed<SourcePath>],
         parameters: &Parameters,
     ) -> GuideRequirement {
-        if parameters.force_image_page_fallback_size {
+        if parameters.force_image_page_fallback_size
+            || parameters.image_page_sizing == ImagePageSizing::MatchImage
+        {
             return GuideRequirement::SizeInformationNotNeeded;
         }
         let mut document_before_image = false;
@@ -181,6 +183,21 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_can_create_guide_docs_after_images_matching_pages() {
+        let source_paths = indexise(&[libre(), image(), image(), image()]);
+        assert_eq!(
+            SizeGuide::need_to_wait_for_pdf_threads(
+                &source_paths,
+                &Parameters {
+                    image_page_sizing: ImagePageSizing::MatchImage,
+                    ..Default::default()
+                }
+            ),
+            GuideRequirement::SizeInformationNotNeeded
+        )
+    }
+
     #[test]
     fn test_can_create_guide_pdf_after_images() {
         let source_paths = indexise(&[image(), image(), image(), libre()]);
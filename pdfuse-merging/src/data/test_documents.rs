@@ -0,0 +1,28 @@
+use lopdf::{dictionary, Document, Object, Stream};
+
+/// A document with the given number of 100 by 100 pages, each filling a small square, in a flat page tree.
+pub(crate) fn document_with_pages(count: usize) -> Document {
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let kids: Vec<Object> = (0..count)
+        .map(|_| {
+            let content_id =
+                document.add_object(Stream::new(dictionary! {}, b"0 0 10 10 re f".to_vec()));
+            document
+                .add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "MediaBox" => vec![0.into(), 0.into(), 100.into(), 100.into()],
+                    "Contents" => content_id,
+                })
+                .into()
+        })
+        .collect();
+    document.objects.insert(
+        pages_id,
+        dictionary! { "Type" => "Pages", "Count" => count as i64, "Kids" => kids }.into(),
+    );
+    let catalog_id = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+    document.trailer.set("Root", catalog_id);
+    document
+}
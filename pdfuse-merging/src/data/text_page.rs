@@ -0,0 +1,126 @@
+use image::DynamicImage;
+use lopdf::Document;
+use pdfuse_sizing::{CustomSize, Length};
+use printpdf::{Op, PdfDocument, PdfPage, PdfSaveOptions, PdfWarnMsg, Point, Pt, XObjectTransform};
+
+use super::{helvetica, imager::dynamic_to_pdf};
+
+const FONT_SIZE: f32 = 12.0;
+const LINE_HEIGHT: f32 = 16.0;
+/// Distance between the text and the edges of the page, in points.
+const PAGE_MARGIN: f32 = 36.0;
+/// Longest side of a thumbnail, in pixels.
+const THUMBNAIL_PIXELS: u32 = 1024;
+
+/// A page with lines of text, written from the top left corner downwards.
+///
+/// Used for pages that do not come from any input file. The text is written in Helvetica, with characters it cannot
+/// write replaced.
+pub struct TextPage {
+    page_size: CustomSize,
+    lines: Vec<String>,
+    thumbnail: Option<DynamicImage>,
+}
+
+impl TextPage {
+    pub fn new(page_size: impl Into<CustomSize>) -> Self {
+        TextPage {
+            page_size: page_size.into(),
+            lines: vec![],
+            thumbnail: None,
+        }
+    }
+
+    /// Sets an image to be shown in the lower half of the page.
+    pub fn set_thumbnail(&mut self, image: DynamicImage) -> &mut Self {
+        self.thumbnail = Some(image.thumbnail(THUMBNAIL_PIXELS, THUMBNAIL_PIXELS));
+        self
+    }
+
+    /// Adds a line of text, wrapping it if it would not fit in the page width.
+    pub fn add_line(&mut self, line: impl AsRef<str>) -> &mut Self {
+        let usable_width = (self.page_size.horizontal.pt() as f32 - 2.0 * PAGE_MARGIN) / FONT_SIZE;
+        let mut wrapped = String::new();
+        for c in line.as_ref().chars() {
+            wrapped.push(c);
+            // every line keeps at least one character
+            if wrapped.chars().nth(1).is_some()
+                && helvetica::width(&helvetica::encode(&wrapped).bytes) > usable_width as f64
+            {
+                wrapped.pop();
+                self.lines.push(std::mem::take(&mut wrapped));
+                wrapped.push(c);
+            }
+        }
+        self.lines.push(wrapped);
+        self
+    }
+
+    pub fn into_ops(self) -> Vec<Op> {
+        let top = Length::from_points(self.page_size.vertical.pt() - (PAGE_MARGIN + FONT_SIZE) as f64);
+        let mut ops = vec![
+            Op::StartTextSection,
+            helvetica::set_font_op(FONT_SIZE),
+            Op::SetLineHeight {
+                lh: Pt(LINE_HEIGHT),
+            },
+            Op::SetTextCursor {
+                pos: Point {
+                    x: Pt(PAGE_MARGIN),
+                    y: top.into(),
+                },
+            },
+        ];
+        for line in self.lines {
+            ops.extend(helvetica::write_ops(helvetica::encode(&line).bytes));
+            ops.push(Op::AddLineBreak);
+        }
+        ops.push(Op::EndTextSection);
+        ops
+    }
+
+    pub fn into_document(mut self) -> Document {
+        let page_size = self.page_size;
+        let mut document = PdfDocument::new("text");
+        let thumbnail = self.thumbnail.take();
+        let mut ops = self.into_ops();
+        if let Some(image) = thumbnail {
+            ops.extend(thumbnail_ops(&mut document, image, page_size));
+        }
+        let page = PdfPage::new(page_size.horizontal.into(), page_size.vertical.into(), ops);
+        let mut warnings: Vec<PdfWarnMsg> = vec![];
+        // the text is written with operations printpdf does not know, see [`helvetica::write_ops`]
+        let save_options = PdfSaveOptions {
+            secure: false,
+            ..Default::default()
+        };
+        let bytes = document
+            .with_pages(vec![page])
+            .save(&save_options, &mut warnings);
+        Document::load_mem(&bytes).unwrap()
+    }
+}
+
+/// Adds the image to the document and returns operations drawing it centered in the lower half of the page.
+fn thumbnail_ops(document: &mut PdfDocument, image: DynamicImage, page_size: CustomSize) -> Vec<Op> {
+    let area_width = page_size.horizontal.pt() as f32 - 2.0 * PAGE_MARGIN;
+    let area_height = page_size.vertical.pt() as f32 / 2.0 - PAGE_MARGIN;
+    // at 72 DPI one pixel is one point
+    let (width, height) = (image.width() as f32, image.height() as f32);
+    let scale = (area_width / width).min(area_height / height);
+    let Ok(raw_image) = dynamic_to_pdf(image) else {
+        return vec![];
+    };
+    let image_id = document.add_image(&raw_image);
+    vec![Op::UseXobject {
+        id: image_id,
+        transform: XObjectTransform {
+            translate_x: Some(Pt(PAGE_MARGIN + (area_width - width * scale) / 2.0)),
+            translate_y: Some(Pt(PAGE_MARGIN)),
+            scale_x: Some(scale),
+            scale_y: Some(scale),
+            dpi: Some(72.0),
+            rotate: None,
+        },
+    }]
+}
@@ -0,0 +1,308 @@
+use std::io::{Cursor, Read, Seek};
+
+use image::{
+    metadata::Orientation, DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, Luma, LumaA,
+    Pixel, Rgb, Rgb32FImage, RgbImage, Rgba, Rgba32FImage, RgbaImage,
+};
+use tiff::{
+    decoder::{ifd::Value, Decoder, DecodingResult},
+    tags::Tag,
+    ColorType,
+};
+
+use super::{
+    loaded_image::Decoded,
+    resolution::{self, Resolution},
+    tone_map,
+};
+use crate::error::ImageLoadError;
+
+/// The pages of a TIFF file, each decoded when it is reached, along with its resolution if it is plausible.
+///
+/// Iteration stops after the first page that cannot be decoded.
+pub(crate) struct TiffPages<'a> {
+    decoder: Option<Decoder<Cursor<&'a [u8]>>>,
+    ignore_orientation: bool,
+    started: bool,
+}
+
+impl<'a> TiffPages<'a> {
+    pub fn new(bytes: &'a [u8], ignore_orientation: bool) -> Result<Self, ImageLoadError> {
+        Ok(TiffPages {
+            decoder: Some(Decoder::new(Cursor::new(bytes))?),
+            ignore_orientation,
+            started: false,
+        })
+    }
+}
+
+impl Iterator for TiffPages<'_> {
+    type Item = Result<(Decoded, Option<Resolution>), ImageLoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let decoder = self.decoder.as_mut()?;
+        // the decoder starts at the first page and refuses to follow directories linked in a loop
+        if self.started {
+            let next = match decoder.more_images() {
+                true => decoder.next_image(),
+                false => {
+                    self.decoder = None;
+                    return None;
+                }
+            };
+            if let Err(err) = next {
+                self.decoder = None;
+                return Some(Err(err.into()));
+            }
+        }
+        self.started = true;
+        let page = decode_page(decoder, self.ignore_orientation);
+        if page.is_err() {
+            self.decoder = None;
+        }
+        Some(page)
+    }
+}
+
+/// Decodes the current page of the decoder, turning it upright according to its orientation tag unless
+/// `ignore_orientation` is set.
+///
+/// CMYK pages keep their CMYK pixels, see [`Decoded::cmyk`].
+fn decode_page<R: Read + Seek>(
+    decoder: &mut Decoder<R>,
+    ignore_orientation: bool,
+) -> Result<(Decoded, Option<Resolution>), ImageLoadError> {
+    // a broken orientation tag is not worth failing the whole page
+    let orientation = decoder
+        .find_tag(Tag::Orientation)
+        .ok()
+        .flatten()
+        .and_then(|x| Orientation::from_exif(x.into_u16().ok()?.min(255) as u8))
+        .filter(|x| *x != Orientation::NoTransforms && !ignore_orientation);
+    let icc_profile = decoder.get_tag_u8_vec(Tag::IccProfile).ok();
+    let page_resolution = read_resolution(decoder);
+    let (width, height) = decoder.dimensions()?;
+    let color_type = decoder.colortype()?;
+    let mut samples = DecodingResult::U8(vec![]);
+    let layout = decoder.read_image_to_buffer(&mut samples)?;
+    if samples.as_buffer(0).as_bytes().len() < layout.complete_len {
+        return Err(ImageLoadError::UnknownPixelType);
+    }
+    let mut image = to_image(width, height, color_type, samples, layout.planes)
+        .ok_or(ImageLoadError::UnknownPixelType)?;
+    if let Some(orientation) = orientation {
+        image.apply_orientation(orientation);
+    }
+    let decoded = Decoded {
+        image: tone_map::to_8_bit(image),
+        orientation,
+        icc_profile,
+        cmyk: matches!(color_type, ColorType::CMYK(_)),
+        date_taken: None,
+    };
+    let page_resolution = page_resolution.map(|x| resolution::turned(x, orientation));
+    Ok((decoded, page_resolution))
+}
+
+/// Puts the samples into an image of the matching type, with CMYK in the channels of an RGBA image.
+///
+/// Returns `None` for color types and bit depths that are not supported.
+fn to_image(
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    samples: DecodingResult,
+    planes: usize,
+) -> Option<DynamicImage> {
+    use DecodingResult::{F32, U16, U8};
+    let image = match (color_type, samples) {
+        (ColorType::Gray(1), U8(samples)) => {
+            let row_length = width.div_ceil(8) as usize;
+            let rows = samples.chunks_exact(row_length).take(height as usize);
+            let pixels = rows
+                .flat_map(|row| {
+                    (0..width as usize).map(move |x| match row[x / 8] & (0x80 >> (x % 8)) {
+                        0 => 0,
+                        _ => 255,
+                    })
+                })
+                .collect();
+            GrayImage::from_raw(width, height, pixels)?.into()
+        }
+        (ColorType::Gray(8), U8(samples)) => GrayImage::from_raw(width, height, samples)?.into(),
+        (ColorType::Gray(16), U16(samples)) => {
+            ImageBuffer::<Luma<u16>, _>::from_raw(width, height, samples)?.into()
+        }
+        (ColorType::GrayA(8), U8(samples)) => {
+            GrayAlphaImage::from_raw(width, height, interleaved::<LumaA<u8>>(samples, planes))?
+                .into()
+        }
+        (ColorType::GrayA(16), U16(samples)) => ImageBuffer::<LumaA<u16>, _>::from_raw(
+            width,
+            height,
+            interleaved::<LumaA<u16>>(samples, planes),
+        )?
+        .into(),
+        (ColorType::RGB(8), U8(samples)) => {
+            RgbImage::from_raw(width, height, interleaved::<Rgb<u8>>(samples, planes))?.into()
+        }
+        (ColorType::RGB(16), U16(samples)) => ImageBuffer::<Rgb<u16>, _>::from_raw(
+            width,
+            height,
+            interleaved::<Rgb<u16>>(samples, planes),
+        )?
+        .into(),
+        (ColorType::RGB(32), F32(samples)) => {
+            Rgb32FImage::from_raw(width, height, interleaved::<Rgb<f32>>(samples, planes))?.into()
+        }
+        (ColorType::RGBA(8) | ColorType::CMYK(8), U8(samples)) => {
+            RgbaImage::from_raw(width, height, interleaved::<Rgba<u8>>(samples, planes))?.into()
+        }
+        (ColorType::RGBA(16), U16(samples)) => ImageBuffer::<Rgba<u16>, _>::from_raw(
+            width,
+            height,
+            interleaved::<Rgba<u16>>(samples, planes),
+        )?
+        .into(),
+        (ColorType::CMYK(16), U16(samples)) => {
+            let samples = interleaved::<Rgba<u16>>(samples, planes);
+            let samples = samples.into_iter().map(|x| (x >> 8) as u8).collect();
+            RgbaImage::from_raw(width, height, samples)?.into()
+        }
+        (ColorType::RGBA(32), F32(samples)) => {
+            Rgba32FImage::from_raw(width, height, interleaved::<Rgba<f32>>(samples, planes))?
+                .into()
+        }
+        _ => return None,
+    };
+    Some(image)
+}
+
+/// Interleaves samples stored as one plane per channel, returns other samples as they are.
+fn interleaved<P: Pixel>(samples: Vec<P::Subpixel>, planes: usize) -> Vec<P::Subpixel> {
+    let channels = P::CHANNEL_COUNT as usize;
+    if planes != channels {
+        return samples;
+    }
+    let plane_length = samples.len() / channels;
+    (0..plane_length)
+        .flat_map(|index| (0..channels).map(move |channel| (index, channel)))
+        .map(|(index, channel)| samples[channel * plane_length + index])
+        .collect()
+}
+
+/// Resolution of the current page, if it is plausible.
+fn read_resolution<R: Read + Seek>(decoder: &mut Decoder<R>) -> Option<Resolution> {
+    let mut decimal = |tag| decoder.find_tag(tag).ok().flatten().and_then(to_decimal);
+    let horizontal = decimal(Tag::XResolution)?;
+    let vertical = decimal(Tag::YResolution).unwrap_or(horizontal);
+    // inches, unless the unit says otherwise
+    let per_centimeter = match decimal(Tag::ResolutionUnit).unwrap_or(2.0) as u64 {
+        2 => false,
+        3 => true,
+        _ => return None,
+    };
+    resolution::plausible(resolution::per_inch(horizontal, vertical, per_centimeter))
+}
+
+fn to_decimal(value: Value) -> Option<f64> {
+    match value {
+        Value::Rational(numerator, denominator) => {
+            (denominator != 0).then(|| numerator as f64 / denominator as f64)
+        }
+        Value::Float(value) => Some(value as f64),
+        Value::Double(value) => Some(value),
+        value => value.into_u64().ok().map(|x| x as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tiff::{
+        encoder::{
+            colortype::{Gray8, CMYK8},
+            Rational, TiffEncoder, TiffKind,
+        },
+        tags::ResolutionUnit,
+    };
+
+    use super::*;
+
+    fn write_pages<K: TiffKind>(
+        mut encoder: TiffEncoder<&mut Cursor<Vec<u8>>, K>,
+        pages: &[(u32, u32, u8)],
+    ) {
+        for &(width, height, value) in pages {
+            let data = vec![value; (width * height) as usize];
+            encoder.write_image::<Gray8>(width, height, &data).unwrap();
+        }
+    }
+
+    fn tiff(big: bool, pages: &[(u32, u32, u8)]) -> Vec<u8> {
+        let mut bytes = Cursor::new(vec![]);
+        match big {
+            false => write_pages(TiffEncoder::new(&mut bytes).unwrap(), pages),
+            true => write_pages(TiffEncoder::new_big(&mut bytes).unwrap(), pages),
+        }
+        bytes.into_inner()
+    }
+
+    fn decode_pages(bytes: &[u8]) -> Vec<(Decoded, Option<Resolution>)> {
+        TiffPages::new(bytes, false)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn reads_every_page() {
+        for big in [false, true] {
+            let bytes = tiff(big, &[(3, 2, 10), (1, 4, 200)]);
+            let pages: Vec<_> = decode_pages(&bytes)
+                .into_iter()
+                .map(|(page, _)| page.image)
+                .collect();
+            assert_eq!(pages.len(), 2);
+            assert_eq!((pages[0].width(), pages[0].height()), (3, 2));
+            assert_eq!((pages[1].width(), pages[1].height()), (1, 4));
+            assert_eq!(pages[1].to_luma8().get_pixel(0, 3).0, [200]);
+        }
+    }
+
+    #[test]
+    fn reads_resolution() {
+        let mut bytes = Cursor::new(vec![]);
+        let mut encoder = TiffEncoder::new(&mut bytes).unwrap();
+        let mut image = encoder.new_image::<Gray8>(2, 2).unwrap();
+        image.resolution(ResolutionUnit::Centimeter, Rational { n: 80, d: 1 });
+        image.write_data(&[0; 4]).unwrap();
+        let mut image = encoder.new_image::<Gray8>(2, 2).unwrap();
+        image.resolution_unit(ResolutionUnit::Inch);
+        image.x_resolution(Rational { n: 204, d: 1 });
+        image.y_resolution(Rational { n: 196, d: 1 });
+        image.write_data(&[0; 4]).unwrap();
+        let resolutions: Vec<_> = decode_pages(&bytes.into_inner())
+            .into_iter()
+            .map(|(_, resolution)| resolution)
+            .collect();
+        assert_eq!(resolutions, [Some((203.2, 203.2)), Some((204.0, 196.0))]);
+    }
+
+    #[test]
+    fn keeps_cmyk() {
+        let pixels: Vec<u8> = (0..64).collect();
+        let mut bytes = Cursor::new(vec![]);
+        TiffEncoder::new(&mut bytes)
+            .unwrap()
+            .write_image::<CMYK8>(4, 4, &pixels)
+            .unwrap();
+        let (page, _) = decode_pages(&bytes.into_inner()).remove(0);
+        assert!(page.cmyk);
+        assert_eq!(page.image.into_rgba8().into_raw(), pixels);
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(TiffPages::new(b"not a tiff", false).is_err());
+    }
+}
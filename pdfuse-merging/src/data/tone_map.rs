@@ -0,0 +1,60 @@
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+
+/// Linear values below which the sRGB transfer curve is a straight line.
+const SRGB_LINEAR_LIMIT: f32 = 0.0031308;
+
+/// Brings images with floating point components, as decoded from Radiance HDR and OpenEXR files, down to 8 bits.
+///
+/// Their components are linear light without an upper limit, so bright parts are compressed with the Reinhard
+/// operator before the sRGB transfer curve is applied. Other images are returned as they are.
+pub(crate) fn to_8_bit(image: DynamicImage) -> DynamicImage {
+    match image {
+        DynamicImage::ImageRgb32F(buffer) => {
+            RgbImage::from_fn(buffer.width(), buffer.height(), |x, y| {
+                Rgb(buffer.get_pixel(x, y).0.map(tone_map))
+            })
+            .into()
+        }
+        DynamicImage::ImageRgba32F(buffer) => {
+            RgbaImage::from_fn(buffer.width(), buffer.height(), |x, y| {
+                let [red, green, blue, alpha] = buffer.get_pixel(x, y).0;
+                let alpha = (alpha.clamp(0.0, 1.0) * 255.0).round() as u8;
+                Rgba([tone_map(red), tone_map(green), tone_map(blue), alpha])
+            })
+            .into()
+        }
+        other => other,
+    }
+}
+
+fn tone_map(value: f32) -> u8 {
+    // written so that infinite values become white, while NaN and negative values become black
+    let compressed = 1.0 - 1.0 / (1.0 + value.max(0.0));
+    let encoded = match compressed <= SRGB_LINEAR_LIMIT {
+        true => compressed * 12.92,
+        false => 1.055 * compressed.powf(1.0 / 2.4) - 0.055,
+    };
+    (encoded * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgb32FImage;
+
+    use super::*;
+
+    #[test]
+    fn compresses_bright_values() {
+        let values = [0.0, 0.18, 1.0, 100.0, f32::INFINITY, -1.0];
+        let image =
+            Rgb32FImage::from_fn(values.len() as u32, 1, |x, _| Rgb([values[x as usize]; 3]));
+        let mapped = to_8_bit(image.into()).to_rgb8();
+        let levels: Vec<u8> = mapped.pixels().map(|pixel| pixel.0[0]).collect();
+        assert_eq!(levels[0], 0);
+        assert!(levels.windows(2).take(3).all(|pair| pair[0] < pair[1]));
+        assert!((180..200).contains(&levels[2]));
+        assert!(levels[3] > 250);
+        assert_eq!(levels[4], 255);
+        assert_eq!(levels[5], 0);
+    }
+}
@@ -51,6 +51,11 @@ impl From<std::io::Error> for DocumentLoadError {
         Self::Io(value)
     }
 }
+impl From<ImageLoadError> for DocumentLoadError {
+    fn from(value: ImageLoadError) -> Self {
+        Self::InvalidImage(value)
+    }
+}
 impl Display for DocumentLoadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -66,13 +71,79 @@ impl Error for DocumentLoadError{}
 #[derive(Debug)]
 pub enum ImageLoadError{
     UnknownFormat,
-    UnknownPixelType
+    UnknownPixelType,
+    /// A JPEG 2000 file without an image header or a codestream.
+    InvalidJpeg2000,
+    Decoding(image::ImageError),
+    /// A TIFF page that could not be read.
+    Tiff(tiff::TiffError),
 }
 impl Display for ImageLoadError{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self{
             ImageLoadError::UnknownFormat =>write_t!(f,"image_invalid_format"),
             ImageLoadError::UnknownPixelType => write_t!(f,"image_invalid_pixel_type"),
+            ImageLoadError::InvalidJpeg2000 => write_t!(f, "error.invalid_jpeg2000"),
+            ImageLoadError::Decoding(error) => error.fmt(f),
+            ImageLoadError::Tiff(error) => error.fmt(f),
         }
     }
-}
\ No newline at end of file
+}
+impl From<image::ImageError> for ImageLoadError {
+    fn from(value: image::ImageError) -> Self {
+        Self::Decoding(value)
+    }
+}
+impl From<tiff::TiffError> for ImageLoadError {
+    fn from(value: tiff::TiffError) -> Self {
+        Self::Tiff(value)
+    }
+}
+
+/// Errors that prevent the merged file from being written at all.
+#[derive(Debug)]
+pub enum MergeError {
+    /// None of the inputs produced a document.
+    NothingToMerge,
+    /// Aborted because of an input error, as requested by the error policy.
+    Aborted,
+    /// Could not split the inputs into fronts and backs for collating.
+    CollateSplit,
+    /// The merged document lacks a required root object.
+    RootNotFound(&'static str),
+    Save(std::io::Error),
+}
+impl From<std::io::Error> for MergeError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Save(value)
+    }
+}
+impl Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::NothingToMerge => write_t!(f, "error.nothing_to_merge"),
+            MergeError::Aborted => write_t!(f, "error.aborted"),
+            MergeError::CollateSplit => write_t!(f, "error.collate_split"),
+            MergeError::RootNotFound(item) => write_t!(f, "debug.root_not_found", item = item),
+            MergeError::Save(error) => error.fmt(f),
+        }
+    }
+}
+impl Error for MergeError {}
+
+#[derive(Debug)]
+pub enum ReportError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Toml(toml::ser::Error),
+}
+impl Display for ReportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportError::Io(error) => error.fmt(f),
+            ReportError::Json(error) => error.fmt(f),
+            ReportError::Toml(error) => error.fmt(f),
+        }
+    }
+}
+impl Error for ReportError {}
@@ -7,6 +7,8 @@
 #![feature(inherent_associated_types)]
 mod data;
 mod error;
-pub use data::load;
-pub use error::{DocumentLoadError, LibreConversionError};
+mod report;
+pub use data::{load, MergeOutcome};
+pub use error::{DocumentLoadError, LibreConversionError, MergeError, ReportError};
+pub use report::{MergeReport, PhaseTimings, PrunedObjects, SourceReport};
 rust_i18n::i18n!();
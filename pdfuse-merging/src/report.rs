@@ -0,0 +1,205 @@
+use std::{fs, path::Path, sync::Mutex, time::Duration};
+
+use dashmap::DashMap;
+use pdfuse_parameters::SourcePath;
+use pdfuse_sizing::CustomSize;
+use pdfuse_utils::Indexed;
+use serde::Serialize;
+
+use crate::{error::ReportError, DocumentLoadError, MergeOutcome};
+
+/// Machine-readable summary of a merge, saved with `--report`.
+#[derive(Debug, Clone, Serialize)]
+pub struct MergeReport {
+    pub output_file: String,
+    /// Size of the output file in bytes, if it was written.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_size: Option<u64>,
+    pub outcome: MergeOutcome,
+    pub timings: PhaseTimings,
+    pub pruned: PrunedObjects,
+    pub sources: Vec<SourceReport>,
+}
+
+/// Duration of each phase of the merge, in seconds.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PhaseTimings {
+    pub loading: f64,
+    pub conversion: f64,
+    pub merging: f64,
+}
+
+/// Unreferenced objects left out of the output file.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PrunedObjects {
+    pub objects: usize,
+    /// Approximate size of the objects in bytes.
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Phase {
+    Loading,
+    Conversion,
+    Merging,
+}
+
+/// Everything known about a single input after the merge.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceReport {
+    pub index: usize,
+    pub path: String,
+    /// One of `image`, `pdf` or `document`.
+    pub kind: &'static str,
+    /// Number of pages this input contributed to the output.
+    pub page_count: usize,
+    /// First page of this input in the output, counted from 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_output_page: Option<usize>,
+    /// Last page of this input in the output, counted from 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_output_page: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_size: Option<CustomSize>,
+    /// Pages left out as blank, counted from 1 within this input.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub removed_pages: Vec<usize>,
+    /// Time spent reading the file (and converting it to PDF for office documents), in seconds.
+    pub load_time: f64,
+    /// Time spent converting the loaded file to a PDF document, in seconds.
+    pub convert_time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SourceReport {
+    fn new(index: usize, source: &SourcePath) -> Self {
+        let kind = match source {
+            SourcePath::Image(_) => "image",
+            SourcePath::Pdf(_) => "pdf",
+            SourcePath::LibreDocument(_) => "document",
+        };
+        SourceReport {
+            index,
+            path: source.to_string(),
+            kind,
+            page_count: 0,
+            first_output_page: None,
+            last_output_page: None,
+            page_size: None,
+            removed_pages: vec![],
+            load_time: 0.0,
+            convert_time: 0.0,
+            error: None,
+        }
+    }
+}
+
+/// Collects information for the [`MergeReport`] while the files are processed.
+///
+/// Shared between threads, so every method takes `&self`.
+#[derive(Debug, Default)]
+pub(crate) struct ReportBuilder {
+    sources: DashMap<usize, SourceReport>,
+    timings: Mutex<PhaseTimings>,
+    pruned: Mutex<PrunedObjects>,
+}
+
+impl ReportBuilder {
+    pub fn new(sources: &[Indexed<SourcePath>]) -> Self {
+        let map = sources
+            .iter()
+            .map(|x| (x.index(), SourceReport::new(x.index(), x.value())))
+            .collect();
+        ReportBuilder {
+            sources: map,
+            timings: Default::default(),
+            pruned: Default::default(),
+        }
+    }
+
+    fn update(&self, index: usize, f: impl FnOnce(&mut SourceReport)) {
+        if let Some(mut entry) = self.sources.get_mut(&index) {
+            f(&mut entry);
+        }
+    }
+
+    pub fn record_load(&self, index: usize, duration: Duration) {
+        self.update(index, |s| s.load_time += duration.as_secs_f64());
+    }
+
+    pub fn record_conversion(&self, index: usize, duration: Duration) {
+        self.update(index, |s| s.convert_time += duration.as_secs_f64());
+    }
+
+    pub fn record_error(&self, index: usize, error: &DocumentLoadError) {
+        self.update(index, |s| s.error = Some(error.to_string()));
+    }
+
+    pub fn record_removed_pages(&self, index: usize, pages: impl IntoIterator<Item = usize>) {
+        self.update(index, |s| s.removed_pages.extend(pages));
+    }
+
+    /// Records where (some of) the pages of the input ended up. `first_page` is counted from 1.
+    ///
+    /// Can be called many times for the same input, if its pages are not next to each other.
+    pub fn record_output(
+        &self,
+        index: usize,
+        first_page: usize,
+        page_count: usize,
+        page_size: Option<CustomSize>,
+    ) {
+        self.update(index, |s| {
+            s.page_count += page_count;
+            s.page_size = s.page_size.or(page_size);
+            if page_count > 0 {
+                s.first_output_page.get_or_insert(first_page);
+                s.last_output_page = Some(first_page + page_count - 1);
+            }
+        });
+    }
+
+    pub fn record_phase(&self, phase: Phase, duration: Duration) {
+        let mut timings = self.timings.lock().expect("Report lock should not be poisoned");
+        let seconds = duration.as_secs_f64();
+        match phase {
+            Phase::Loading => timings.loading += seconds,
+            Phase::Conversion => timings.conversion += seconds,
+            Phase::Merging => timings.merging += seconds,
+        }
+    }
+
+    pub fn record_pruned(&self, pruned: PrunedObjects) {
+        *self.pruned.lock().expect("Report lock should not be poisoned") = pruned;
+    }
+
+    pub fn build(&self, output_file: &str, outcome: MergeOutcome) -> MergeReport {
+        let mut sources: Vec<SourceReport> =
+            self.sources.iter().map(|x| x.value().clone()).collect();
+        sources.sort_by_key(|x| x.index);
+        MergeReport {
+            output_file: output_file.to_owned(),
+            output_size: fs::metadata(output_file).ok().map(|m| m.len()),
+            outcome,
+            timings: *self.timings.lock().expect("Report lock should not be poisoned"),
+            pruned: *self.pruned.lock().expect("Report lock should not be poisoned"),
+            sources,
+        }
+    }
+}
+
+impl MergeReport {
+    /// Saves the report as TOML if `path` has the `toml` extension, otherwise as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), ReportError> {
+        let path = path.as_ref();
+        let is_toml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+        let content = match is_toml {
+            true => toml::to_string_pretty(self).map_err(ReportError::Toml)?,
+            false => serde_json::to_string_pretty(self).map_err(ReportError::Json)?,
+        };
+        fs::write(path, content).map_err(ReportError::Io)
+    }
+}
@@ -1,18 +1,21 @@
 use crate::{
     errors::ConfigError,
     file_finder,
+    filters::{ImageFilter, InputFilters},
+    grid::Grid,
     parameters::{Parameters, ParametersWithPaths},
     paths::{self, expand_path},
+    placement::{Alignment, InputPlacement, PlacementMode},
     SourcePath,
 };
 use clap::{
     builder::styling, ArgAction, ColorChoice, CommandFactory, FromArgMatches, Parser, ValueEnum,
     ValueHint,
 };
-use pdfuse_sizing::{CustomSize, IsoPaper, PageSize};
-use pdfuse_utils::Indexed;
+use pdfuse_sizing::{CustomSize, IsoPaper, Length, LengthParseError, Margins, PageSize};
+use pdfuse_utils::{warn_t, Indexed};
 use rust_i18n::t;
-use serde::{ Deserialize, Serialize};
+use serde::{ Deserialize, Deserializer, Serialize};
 use std::{
     env,
     ffi::OsString,
@@ -48,6 +51,20 @@ fn get_default_libre() -> Vec<String> {
     DEFAULT_LIBRE_PATHS.iter().map(|p| p.to_string()).collect()
 }
 
+/// Parses margins, warning about those which meant the total of both sides before, see [`Margins::meaning_changed`].
+fn parse_margins(text: &str) -> Result<Margins, LengthParseError> {
+    let margins = Margins::try_from_string(text)?;
+    if Margins::meaning_changed(text) {
+        warn_t!("warning.margin_per_side", margin = text);
+    }
+    Ok(margins)
+}
+
+fn deserialize_margins<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Margins, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    parse_margins(&text).map_err(serde::de::Error::custom)
+}
+
 /// Replaces the `$field` in `$parsed` with the value from `$loaded` if `$field` was not specified in the commandline.
 macro_rules! hack {
     (mut $parsed:expr,$loaded:expr,$field:ident,$matches:expr) => {{
@@ -87,6 +104,99 @@ impl From<LogLevel> for log::LevelFilter {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Log the error and leave the file out of the output.
+    #[default]
+    Skip,
+    /// Stop at the first error without writing the output file.
+    Abort,
+    /// Insert a page describing the error in place of the file.
+    Placeholder,
+}
+
+/// Version of the PDF specification declared in the output file.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PdfVersion {
+    #[value(name = "1.4")]
+    #[serde(rename = "1.4")]
+    V1_4,
+    #[default]
+    #[value(name = "1.5")]
+    #[serde(rename = "1.5")]
+    V1_5,
+    #[value(name = "1.6")]
+    #[serde(rename = "1.6")]
+    V1_6,
+    #[value(name = "1.7")]
+    #[serde(rename = "1.7")]
+    V1_7,
+    #[value(name = "2.0")]
+    #[serde(rename = "2.0")]
+    V2_0,
+}
+
+impl PdfVersion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PdfVersion::V1_4 => "1.4",
+            PdfVersion::V1_5 => "1.5",
+            PdfVersion::V1_6 => "1.6",
+            PdfVersion::V1_7 => "1.7",
+            PdfVersion::V2_0 => "2.0",
+        }
+    }
+}
+
+/// How to deal with images whose orientation does not match the page.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+pub enum AutoOrient {
+    /// Turn the page to match the image.
+    Page,
+    /// Rotate the image by 90 degrees, keeping the orientation of the page.
+    Image,
+}
+
+/// How the size of image pages is chosen.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImagePageSizing {
+    /// Use the size of the preceding document, or the fallback size.
+    #[default]
+    Fallback,
+    /// Make each page exactly the size of its image at the DPI, without margins.
+    MatchImage,
+}
+
+/// What happens to color profiles embedded in images.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorProfiles {
+    /// Embed the profile with the image, so that PDF readers show its colors as intended.
+    #[default]
+    Embed,
+    /// Convert the colors of the image to sRGB and leave the profile out.
+    ConvertToSrgb,
+}
+
+/// Side of the image a caption is written on.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaptionPosition {
+    #[default]
+    Below,
+    Above,
+}
+
+/// Order of the second group of pages when collating double-sided scans.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CollateOrder {
+    /// Backs are in the same order as fronts.
+    Forward,
+    /// Backs are in reverse order, as produced by flipping the stack in a sheet-fed scanner.
+    Reverse,
+}
+
 /// Command-line tool to process directories and files with various options.
 ///
 /// Directories will be searched recursively looking for images, PDFs, and office document formats.
@@ -118,6 +228,13 @@ pub struct Args {
     #[arg(long, alias = "whatif", action = ArgAction::SetTrue, default_value_t = def!(what_if))]
     pub what_if: bool,
 
+    /// List the supported input formats and exit.
+    ///
+    /// Image formats are taken from the image decoders the app was built with.
+    #[arg(long, exclusive = true, action = ArgAction::SetTrue)]
+    #[serde(skip)]
+    pub list_formats: bool,
+
     /// Specify a language file identifier.
     #[arg(short, long, value_name = "IDENTIFIER")]
     pub language: Option<String>,
@@ -135,11 +252,36 @@ pub struct Args {
     #[arg(short = 'p', long,  value_name = "PAGE_SIZE", value_parser =PageSize::try_from_string,default_value_t)]
     pub image_page_fallback_size: PageSize,
 
+    /// How the size of image pages is chosen.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = def!(image_page_sizing))]
+    pub image_page_sizing: ImagePageSizing,
+
+    /// Smallest image page when pages match images.
+    #[arg(long, value_name = "PAGE_SIZE", value_parser = CustomSize::try_from_string, default_value_t = def!(image_page_min_size))]
+    pub image_page_min_size: CustomSize,
+
+    /// Largest image page when pages match images.
+    ///
+    /// Wins over the smallest size for very long or very tall images.
+    #[arg(long, value_name = "PAGE_SIZE", value_parser = CustomSize::try_from_string, default_value_t = def!(image_page_max_size))]
+    pub image_page_max_size: CustomSize,
+
     /// DPI used when adding images.
     #[arg(long, default_value_t = def!(dpi))]
     pub dpi: u16,
 
+    /// Size images by the resolution saved in them (JPEG density, PNG pHYs, TIFF resolution), falling back to --dpi when it is missing or implausible.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(use_image_dpi))]
+    pub use_image_dpi: bool,
+
+    /// What to do with ICC color profiles embedded in JPEG, PNG and TIFF images.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = def!(color_profiles))]
+    pub color_profiles: ColorProfiles,
+
     /// Quality of JPEG image compression.
+    ///
+    /// JPEG files which do not need resizing or turning are embedded as they are.
+    /// Black and white images are always stored losslessly at one bit per pixel.
     #[arg(long, default_value_t = def!(quality))]
     pub quality: u8,
 
@@ -149,13 +291,124 @@ pub struct Args {
     #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(lossless))]
     pub lossless: bool,
 
+    /// Compress images as black and white scans with CCITT Group 4, overriding `--quality` and `--lossless`.
+    ///
+    /// Gray and color pixels turn black or white, which keeps scanned text sharp and small.
+    /// Images which do not look like scanned documents, such as photos, are left as they are.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(bilevel))]
+    pub bilevel: bool,
+
+    /// Gray level (0-255) below which pixels turn black with `--bilevel`.
+    ///
+    /// Chosen for each image by Otsu's method if not given.
+    #[arg(long, value_name = "LEVEL")]
+    pub bilevel_threshold: Option<u8>,
+
     /// Controls which messages are logged into console.
     #[arg(long,value_enum, default_value_t = def!(log))]
     pub log: LogLevel,
 
-    /// Margin for image pages.
-    #[arg(short = 'm', long, value_name = "MARGIN",value_parser =CustomSize::try_from_string,default_value_t= def!(margin))]
-    pub margin: CustomSize,
+    /// What to do when an input file cannot be loaded or converted.
+    #[arg(long, value_enum, default_value_t = def!(on_error))]
+    pub on_error: ErrorPolicy,
+
+    /// Place images as stored, ignoring the orientation saved in their EXIF data.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(ignore_exif_orientation))]
+    pub ignore_exif_orientation: bool,
+
+    /// Use only the first frame of multi-page TIFFs and animated GIFs and WebPs.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(first_frame_only))]
+    pub first_frame_only: bool,
+
+    /// Straighten tilted scans by the angle of their lines of text.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(deskew))]
+    pub deskew: bool,
+
+    /// Cut off uniform borders around images, like the scanner bed.
+    ///
+    /// The optional value is the largest difference of a color channel (0-255) from the border that is still cut off.
+    #[arg(long, value_name = "TOLERANCE", num_args = 0..=1, default_missing_value = "24")]
+    pub auto_crop: Option<u8>,
+
+    /// Filters cleaning up images before they are placed, applied in the given order.
+    #[arg(long, value_enum, value_name = "FILTER", value_delimiter = ',')]
+    pub filters: Vec<ImageFilter>,
+
+    /// Filters for images from the given file or directory, overriding `--filters`.
+    ///
+    /// Can be repeated, the last matching entry is used. `PATH=` turns filters off.
+    #[arg(long, value_name = "PATH=FILTER[,FILTER...]", value_parser = InputFilters::try_from_string)]
+    pub filters_for: Vec<InputFilters>,
+
+    /// Turn image pages (or images) when it lets the image be placed larger.
+    ///
+    /// JPEG 2000 images are embedded without decoding them, so only their pages can turn.
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, default_missing_value = "page")]
+    pub auto_orient: Option<AutoOrient>,
+
+    /// How images are placed in the drawing area of their pages.
+    #[arg(long, value_enum, value_name = "MODE", default_value_t = def!(placement))]
+    pub placement: PlacementMode,
+
+    /// Where images are placed when they do not cover the drawing area exactly.
+    #[arg(long, value_enum, default_value_t = def!(alignment))]
+    pub alignment: Alignment,
+
+    /// Placement of images from the given file or directory, overriding `--placement` and `--alignment`.
+    ///
+    /// Can be repeated, the last matching entry is used.
+    #[arg(long, value_name = "PATH=MODE[,ALIGNMENT]", value_parser = InputPlacement::try_from_string)]
+    pub placement_for: Vec<InputPlacement>,
+
+    /// Margins of image pages, written like in CSS: one length for all sides, two for top and bottom
+    /// and left and right, three for top, left and right and bottom, or four for top, right, bottom and left.
+    ///
+    /// Lengths without a unit take the last one given, e.g. `10 20 mm`.
+    /// `WIDTH x HEIGHT` gives the total horizontal and vertical margin, split evenly between the sides.
+    ///
+    /// Before margins could be set for each side, one or two lengths gave the total horizontal and vertical margin,
+    /// so `10 20 mm` meant what `10 x 20 mm` means now. A warning is shown when such margins are used.
+    #[arg(short = 'm', long, value_name = "MARGIN",value_parser =parse_margins,default_value_t= def!(margin))]
+    #[serde(deserialize_with = "deserialize_margins")]
+    pub margin: Margins,
+
+    /// Swap the left and right margin on even pages, for binding sheets printed on both sides.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(mirror_margins))]
+    pub mirror_margins: bool,
+
+    /// Write a caption made from the template beneath each image, the file name without a template.
+    ///
+    /// `{name}` is replaced with the file name, `{stem}` with the file name without its extension,
+    /// `{path}` with the path of the file, `{date}` with the date the photo was taken (or the file was modified)
+    /// and `{page}` with the number of the page within the file.
+    #[arg(long, value_name = "TEMPLATE", num_args = 0..=1, default_missing_value = "{name}")]
+    pub caption: Option<String>,
+
+    /// Side of the image the caption is written on.
+    #[arg(long, value_enum, default_value_t = def!(caption_position))]
+    pub caption_position: CaptionPosition,
+
+    /// Font size of captions, in points.
+    #[arg(long, value_name = "POINTS", default_value_t = def!(caption_font_size))]
+    pub caption_font_size: f32,
+
+    /// TrueType or OpenType font embedded for captions.
+    ///
+    /// Without one, captions use the standard Helvetica font, which only has Western European characters.
+    #[arg(long, value_name = "FONT_FILEPATH", value_hint = ValueHint::FilePath)]
+    pub caption_font: Option<String>,
+
+    /// Put several images on each page, in a grid of `COLUMNSxROWS` cells, e.g. `2x3`.
+    ///
+    /// Consecutive image inputs flow into the cells, starting a new page when one is full.
+    /// The margins surround the whole grid and captions are written in each cell.
+    /// A JPEG 2000 image, which is embedded without decoding it, starts a page of its own.
+    #[arg(long, value_name = "COLUMNSxROWS", value_parser = Grid::try_from_string)]
+    pub grid: Option<Grid>,
+
+    /// Space between the cells of the grid.
+    #[arg(long, value_name = "LENGTH", value_parser = Length::try_from_string, default_value_t = def!(grid_gutter))]
+    pub grid_gutter: Length,
 
     /// Force the fallback size for image pages, overriding other PDFs.
     #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(force_image_page_fallback_size))]
@@ -165,6 +418,35 @@ pub struct Args {
     #[arg(long,alias="afs", action = ArgAction::SetTrue, default_value_t = def!(alphabetic_file_sorting))]
     pub alphabetic_file_sorting: bool,
 
+    /// Leave out pages which are (nearly) blank.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(remove_blank_pages))]
+    pub remove_blank_pages: bool,
+
+    /// Images with less ink coverage than this percentage are considered blank.
+    #[arg(long, value_name = "PERCENT", default_value_t = def!(blank_threshold))]
+    pub blank_threshold: f32,
+
+    /// Interleave pages of the first half of the inputs (fronts) with the second half (backs).
+    ///
+    /// Without `--collate-fronts`, the inputs are split where the fronts have as many pages as the backs,
+    /// or one more. Merging fails if there is no such split.
+    #[arg(long, value_enum, value_name = "BACKS_ORDER")]
+    pub collate: Option<CollateOrder>,
+
+    /// Number of input files holding the fronts when collating, the remaining ones hold the backs.
+    #[arg(long, value_name = "COUNT")]
+    pub collate_fronts: Option<usize>,
+
+    /// Insert a page describing each input file before its pages.
+    ///
+    /// Cannot be used when collating or laying images out in a grid.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(separator_pages))]
+    pub separator_pages: bool,
+
+    /// Show a thumbnail of image inputs on their separator pages.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(separator_thumbnails))]
+    pub separator_thumbnails: bool,
+
     /// Paths to LibreOffice executables for document conversion.
     #[arg(long, value_name = "LIBREOFFICE_PATH", num_args = 1.., default_values_t = def!(libreoffice_path))]
     pub libreoffice_path: Vec<String>,
@@ -177,6 +459,23 @@ pub struct Args {
     #[arg(short = 'o', long, value_name = "OUTPUT_FILEPATH", value_hint = ValueHint::FilePath)]
     #[serde(skip_serializing)]
     pub output_file: Option<String>,
+
+    /// PDF version of the output file.
+    #[arg(long, value_enum, value_name = "VERSION", default_value_t = def!(pdf_version))]
+    pub pdf_version: PdfVersion,
+
+    /// Pack objects into compressed object streams, with a cross-reference stream.
+    ///
+    /// Makes the output smaller, but needs PDF version 1.5 or newer.
+    #[arg(long, action = ArgAction::SetTrue, default_value_t = def!(object_streams))]
+    pub object_streams: bool,
+
+    /// Save a report of the merge to the given path.
+    ///
+    /// The report is saved as TOML if the path ends with `.toml`, otherwise as JSON.
+    #[arg(long, value_name = "REPORT_FILEPATH", value_hint = ValueHint::FilePath)]
+    #[serde(skip_serializing)]
+    pub report: Option<String>,
 }
 
 impl Args {
@@ -185,6 +484,8 @@ impl Args {
     }
 
     pub fn make_parameters(self) -> Result<ParametersWithPaths, ConfigError> {
+        self.check_conflicts()?;
+        self.check_grid()?;
         self.save_config()?;
         let libreoffice_path = self.check_libre();
         let office_good = libreoffice_path.is_some();
@@ -197,19 +498,114 @@ impl Args {
             libreoffice_path,
             alphabetic_file_sorting: self.alphabetic_file_sorting,
             confirm_exit: self.confirm_exit,
+            image_page_sizing: self.image_page_sizing,
+            image_page_min_size: self.image_page_min_size,
+            image_page_max_size: self.image_page_max_size,
             image_dpi: self.dpi,
+            use_image_dpi: self.use_image_dpi,
+            color_profiles: self.color_profiles,
             image_quality: self.quality,
             image_lossless_compression: self.lossless,
+            bilevel_compression: self.bilevel,
+            bilevel_threshold: self.bilevel_threshold,
+            ignore_exif_orientation: self.ignore_exif_orientation,
+            first_frame_only: self.first_frame_only,
+            deskew: self.deskew,
+            auto_crop_tolerance: self.auto_crop,
+            filters: self.filters.clone(),
+            filters_for: self.get_input_filters(),
+            auto_orient: self.auto_orient,
+            placement: self.placement,
+            alignment: self.alignment,
+            placement_for: self.get_input_placements(),
             force_image_page_fallback_size: self.force_image_page_fallback_size,
             image_page_fallback_size: self.image_page_fallback_size,
             margin: self.margin,
+            mirror_margins: self.mirror_margins,
+            caption: self.caption.clone(),
+            caption_position: self.caption_position,
+            caption_font_size: self.caption_font_size,
+            caption_font: self
+                .caption_font
+                .as_deref()
+                .and_then(paths::expand_path)
+                .map(PathBuf::from),
+            grid: self.grid,
+            grid_gutter: self.grid_gutter,
             what_if: self.what_if,
             recursion_limit: self.recursion_limit,
+            on_error: self.on_error,
+            remove_blank_pages: self.remove_blank_pages,
+            blank_threshold: self.blank_threshold,
+            collate: self.collate,
+            collate_fronts: self.collate_fronts,
+            separator_pages: self.separator_pages,
+            separator_thumbnails: self.separator_thumbnails,
+            pdf_version: self.pdf_version,
+            object_streams: self.object_streams,
             output_file: self.get_output_path(),
+            report_file: self.get_report_path()?,
         };
         Ok(ParametersWithPaths { files, parameters })
     }
 
+    /// Rejects options which cannot be used together.
+    fn check_conflicts(&self) -> Result<(), ConfigError> {
+        if self.collate.is_some() && self.separator_pages {
+            return Err(ConfigError::ConflictingOptions("collate", "separator-pages"));
+        }
+        if self.grid.is_some() && self.separator_pages {
+            return Err(ConfigError::ConflictingOptions("grid", "separator-pages"));
+        }
+        Ok(())
+    }
+
+    /// Checks that the gutters of the grid leave room for its cells on a page of the fallback size.
+    fn check_grid(&self) -> Result<(), ConfigError> {
+        let Some(grid) = self.grid else {
+            return Ok(());
+        };
+        let area = CustomSize::from(self.image_page_fallback_size) - self.margin.total();
+        // a single column or row has no gutters
+        let too_wide = |count: u32, length| count > 1 && self.grid_gutter * (count - 1) >= length;
+        if too_wide(grid.columns, area.horizontal) || too_wide(grid.rows, area.vertical) {
+            return Err(ConfigError::GutterTooWide(self.grid_gutter, grid));
+        }
+        Ok(())
+    }
+
+    /// Expands the path of the report, which does not have to exist yet.
+    fn get_report_path(&self) -> Result<Option<String>, ConfigError> {
+        self.report
+            .as_deref()
+            .map(|path| {
+                paths::expand_path(path).ok_or_else(|| ConfigError::MalformedPath(path.to_owned()))
+            })
+            .transpose()
+    }
+
+    /// Returns per-input placements with their paths expanded and canonicalized, so they can be compared with found files.
+    fn get_input_placements(&self) -> Vec<InputPlacement> {
+        self.placement_for
+            .iter()
+            .map(|x| InputPlacement {
+                path: get_input_path(&x.path),
+                ..x.clone()
+            })
+            .collect()
+    }
+
+    /// Returns per-input filters with their paths expanded and canonicalized, like [`Args::get_input_placements`].
+    fn get_input_filters(&self) -> Vec<InputFilters> {
+        self.filters_for
+            .iter()
+            .map(|x| InputFilters {
+                path: get_input_path(&x.path),
+                ..x.clone()
+            })
+            .collect()
+    }
+
     fn check_libre(&self) -> Option<PathBuf> {
         for libre_path in &self.libreoffice_path {
             let expanded_path = paths::expand_path(libre_path);
@@ -295,8 +691,11 @@ impl Args {
             None => <Self as CommandFactory>::command().get_matches(),
         };
         let args = Self::parse_and_load(matches)?;
-        args.save_config()?;
         args.load_language();
+        // listing the formats does nothing else, like saving the configuration
+        if !args.list_formats {
+            args.save_config()?;
+        }
         Ok(args)
     }
 
@@ -327,15 +726,48 @@ impl Args {
             hack!(mut args, loaded, language, matches); //: None,
             hack!(mut args, loaded, recursion_limit, matches); //: 4,
             hack!(mut args, loaded, image_page_fallback_size, matches); //: IsoPaper::a(4).into(),
+            hack!(mut args, loaded, image_page_sizing, matches); //: ImagePageSizing::Fallback,
+            hack!(mut args, loaded, image_page_min_size, matches); //: CustomSize::from_inches(1.0, 1.0),
+            hack!(mut args, loaded, image_page_max_size, matches); //: CustomSize::from_inches(200.0, 200.0),
             hack!(mut args, loaded, dpi, matches); //: 300,
-            hack!(mut args, loaded, margin, matches); //: CustomSize::zero(),
+            hack!(mut args, loaded, use_image_dpi, matches); //: false,
+            hack!(mut args, loaded, color_profiles, matches); //: ColorProfiles::Embed,
+            hack!(mut args, loaded, margin, matches); //: Margins::zero(),
+            hack!(mut args, loaded, mirror_margins, matches); //: false,
+            hack!(mut args, loaded, caption, matches); //: None,
+            hack!(mut args, loaded, caption_position, matches); //: CaptionPosition::Below,
+            hack!(mut args, loaded, caption_font_size, matches); //: 10.0,
+            hack!(mut args, loaded, caption_font, matches); //: None,
+            hack!(mut args, loaded, grid, matches); //: None,
+            hack!(mut args, loaded, grid_gutter, matches); //: Length::from_millimeters(5.0),
             hack!(mut args, loaded, force_image_page_fallback_size, matches); //: false,
             hack!(mut args, loaded, log, matches); //: depends,
+            hack!(mut args, loaded, on_error, matches); //: ErrorPolicy::Skip,
             hack!(mut args, loaded, quality, matches); //: 95,
             hack!(mut args, loaded, lossless, matches); //: false,
+            hack!(mut args, loaded, bilevel, matches); //: false,
+            hack!(mut args, loaded, bilevel_threshold, matches); //: None,
+            hack!(mut args, loaded, ignore_exif_orientation, matches); //: false,
+            hack!(mut args, loaded, first_frame_only, matches); //: false,
+            hack!(mut args, loaded, deskew, matches); //: false,
+            hack!(mut args, loaded, auto_crop, matches); //: None,
+            hack!(mut args, loaded, filters, matches); //: vec![],
+            hack!(mut args, loaded, filters_for, matches); //: vec![],
+            hack!(mut args, loaded, auto_orient, matches); //: None,
+            hack!(mut args, loaded, placement, matches); //: PlacementMode::Fit,
+            hack!(mut args, loaded, alignment, matches); //: Alignment::Center,
+            hack!(mut args, loaded, placement_for, matches); //: vec![],
             hack!(mut args, loaded, alphabetic_file_sorting, matches); //: false,
+            hack!(mut args, loaded, remove_blank_pages, matches); //: false,
+            hack!(mut args, loaded, blank_threshold, matches); //: 0.5,
+            hack!(mut args, loaded, collate, matches); //: None,
+            hack!(mut args, loaded, collate_fronts, matches); //: None,
+            hack!(mut args, loaded, separator_pages, matches); //: false,
+            hack!(mut args, loaded, separator_thumbnails, matches); //: false,
             hack!(mut args, loaded, libreoffice_path, matches); //: get_default_libre(),
             hack!(mut args, loaded, output_directory, matches); //: ".".to_owned(),
+            hack!(mut args, loaded, pdf_version, matches); //: PdfVersion::V1_5,
+            hack!(mut args, loaded, object_streams, matches); //: false,
         } else if !is_default_config {
             // config does not exist and it is not default
             Err(ConfigError::MissingConfigError(
@@ -353,6 +785,14 @@ impl Args {
     }
 }
 
+/// Expands and canonicalizes a path given for some of the inputs, so it can be compared with found files.
+fn get_input_path(path: &Path) -> PathBuf {
+    let expanded = paths::expand_path(&path.to_string_lossy())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| path.to_owned());
+    fs::canonicalize(&expanded).unwrap_or(expanded)
+}
+
 /// Returns a unique name based on current time (localized).
 fn get_unique_name() -> String {
     let now = chrono::Local::now().format("%Y-%m-%d_%H-%M-%S");
@@ -370,19 +810,55 @@ impl Default for Args {
             save_config: None,
             confirm_exit: false,
             what_if: false,
+            list_formats: false,
             language: None,
             config: None,
             recursion_limit: 4,
             image_page_fallback_size: IsoPaper::a(4).into(),
+            image_page_sizing: ImagePageSizing::Fallback,
+            image_page_min_size: CustomSize::from_inches(1.0, 1.0),
+            // largest page allowed by PDF readers without a user unit
+            image_page_max_size: CustomSize::from_inches(200.0, 200.0),
             dpi: 300,
+            use_image_dpi: false,
+            color_profiles: ColorProfiles::Embed,
             quality: 95,
             lossless: false,
-            margin: CustomSize::zero(),
+            bilevel: false,
+            bilevel_threshold: None,
+            ignore_exif_orientation: false,
+            first_frame_only: false,
+            deskew: false,
+            auto_crop: None,
+            filters: vec![],
+            filters_for: vec![],
+            auto_orient: None,
+            placement: PlacementMode::Fit,
+            alignment: Alignment::Center,
+            placement_for: vec![],
+            margin: Margins::zero(),
+            mirror_margins: false,
+            caption: None,
+            caption_position: CaptionPosition::Below,
+            caption_font_size: 10.0,
+            caption_font: None,
+            grid: None,
+            grid_gutter: Length::from_millimeters(5.0),
             force_image_page_fallback_size: false,
             alphabetic_file_sorting: false,
+            remove_blank_pages: false,
+            blank_threshold: 0.5,
+            collate: None,
+            collate_fronts: None,
+            separator_pages: false,
+            separator_thumbnails: false,
             libreoffice_path: get_default_libre(),
             output_directory: ".".to_owned(),
             output_file: None,
+            pdf_version: PdfVersion::V1_5,
+            object_streams: false,
+            report: None,
+            on_error: ErrorPolicy::Skip,
             log: {
                 #[cfg(debug_assertions)]
                 {
@@ -7,22 +7,56 @@ fn get_changed_args() -> Args {
         files: vec![],
         save_config: None,
         confirm_exit: !def.confirm_exit,
-        quiet: !def.quiet,
         what_if: !def.what_if,
+        list_formats: false,
         language: Some("de".to_owned()),
         config: None,
         recursion_limit: 17,
         image_page_fallback_size: IsoPaper::c(10).into(),
+        image_page_sizing: ImagePageSizing::MatchImage,
+        image_page_min_size: CustomSize::from_inches(3.0, 3.0),
+        image_page_max_size: CustomSize::from_inches(30.0, 30.0),
         dpi: 1337,
+        use_image_dpi: !def.use_image_dpi,
+        color_profiles: ColorProfiles::ConvertToSrgb,
         quality: 13,
-        log: LogLevel::Info,
+        log: LogLevel::Debug,
+        on_error: ErrorPolicy::Abort,
         lossless: true,
-        margin: CustomSize::from_inches(0.5, 0.5),
+        bilevel: !def.bilevel,
+        bilevel_threshold: Some(99),
+        ignore_exif_orientation: !def.ignore_exif_orientation,
+        first_frame_only: !def.first_frame_only,
+        deskew: !def.deskew,
+        auto_crop: Some(40),
+        filters: vec![ImageFilter::AutoLevels, ImageFilter::Sharpen],
+        filters_for: vec![InputFilters::try_from_string("~/whiteboards=adaptive-threshold").unwrap()],
+        auto_orient: Some(AutoOrient::Image),
+        placement: PlacementMode::Tile,
+        alignment: Alignment::BottomRight,
+        placement_for: vec![InputPlacement::try_from_string("~/scans=fill,top").unwrap()],
+        margin: Margins::try_from_string("1in 0.5in").unwrap(),
+        mirror_margins: !def.mirror_margins,
+        caption: Some("{date} - {name}".to_owned()),
+        caption_position: CaptionPosition::Above,
+        caption_font_size: 14.0,
+        caption_font: Some("~/fonts/NotoSans.ttf".to_owned()),
+        grid: Some(Grid::try_from_string("2x3").unwrap()),
+        grid_gutter: Length::from_inches(0.25),
         force_image_page_fallback_size: !def.force_image_page_fallback_size,
         alphabetic_file_sorting: !def.alphabetic_file_sorting,
+        remove_blank_pages: !def.remove_blank_pages,
+        blank_threshold: 2.5,
+        collate: Some(CollateOrder::Reverse),
+        collate_fronts: Some(4),
+        separator_pages: !def.separator_pages,
+        separator_thumbnails: !def.separator_thumbnails,
         libreoffice_path: vec!["/usr/bin/sl".to_owned()],
         output_directory: "~/o".to_owned(),
         output_file: Some("~/o/p.pdf".to_owned()),
+        pdf_version: PdfVersion::V1_7,
+        object_streams: !def.object_streams,
+        report: Some("~/o/report.json".to_owned()),
     };
     assert_ne!(def,res);
     res
@@ -74,7 +108,6 @@ fn find_changes(a: &Args, b: &Args) -> Vec<Diff> {
         a,
         b,
         confirm_exit,
-        quiet,
         what_if,
         language,
         recursion_limit,
@@ -152,3 +185,39 @@ fn implicit_config_load() {
     assert_ne!(a.dpi, default.dpi);
     assert_ne!(a.margin, default.margin);
 }
+
+#[test]
+fn report_path_does_not_have_to_exist() {
+    let args = Args {
+        report: Some("not/created/yet/report.json".to_owned()),
+        ..Args::default()
+    };
+    let path = PathBuf::from(args.get_report_path().unwrap().unwrap());
+    assert!(path.is_absolute());
+    assert!(path.ends_with("not/created/yet/report.json"));
+}
+
+#[test]
+fn gutters_leave_room_for_cells() {
+    let grid = |text: &str, gutter: f64| Args {
+        grid: Some(Grid::try_from_string(text).unwrap()),
+        grid_gutter: Length::from_millimeters(gutter),
+        margin: Margins::zero(),
+        ..Args::default()
+    };
+    // A4 is 210 mm wide, so three columns leave room for gutters narrower than 105 mm
+    assert!(grid("3x1", 100.0).check_grid().is_ok());
+    assert!(grid("3x1", 105.0).check_grid().is_err());
+    assert!(grid("1x1", 500.0).check_grid().is_ok());
+}
+
+#[test]
+fn separator_pages_conflict_with_grid() {
+    let args = Args {
+        grid: Some(Grid::try_from_string("2x2").unwrap()),
+        separator_pages: true,
+        ..Args::default()
+    };
+    assert!(args.check_conflicts().is_err());
+    assert!(Args { separator_pages: false, ..args }.check_conflicts().is_ok());
+}
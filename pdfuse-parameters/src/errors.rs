@@ -1,7 +1,10 @@
 use std::fmt::{Debug, Display};
 
+use pdfuse_sizing::Length;
 use pdfuse_utils::write_t;
 
+use crate::grid::Grid;
+
 #[derive(Debug)]
 pub struct NoValidFilesError {}
 impl Display for NoValidFilesError {
@@ -29,6 +32,66 @@ impl Display for MalformedPathError {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacementParseError {
+    text: String,
+}
+
+impl PlacementParseError {
+    pub fn new(text: &str) -> PlacementParseError {
+        PlacementParseError {
+            text: text.to_owned(),
+        }
+    }
+}
+
+impl Display for PlacementParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_t!(f, "error.invalid_placement", placement = self.text)
+    }
+}
+impl std::error::Error for PlacementParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FiltersParseError {
+    text: String,
+}
+
+impl FiltersParseError {
+    pub fn new(text: &str) -> FiltersParseError {
+        FiltersParseError {
+            text: text.to_owned(),
+        }
+    }
+}
+
+impl Display for FiltersParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_t!(f, "error.invalid_filters", filters = self.text)
+    }
+}
+impl std::error::Error for FiltersParseError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GridParseError {
+    text: String,
+}
+
+impl GridParseError {
+    pub fn new(text: &str) -> GridParseError {
+        GridParseError {
+            text: text.to_owned(),
+        }
+    }
+}
+
+impl Display for GridParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write_t!(f, "error.invalid_grid", grid = self.text)
+    }
+}
+impl std::error::Error for GridParseError {}
+
 #[derive(Debug)]
 pub enum ConfigError {
     Io(std::io::Error),
@@ -37,6 +100,10 @@ pub enum ConfigError {
     NoValidFiles,
     MalformedPath(String),
     MissingConfigError(String),
+    /// Two options were given which cannot be used together, by their long names.
+    ConflictingOptions(&'static str, &'static str),
+    /// The gutters of the grid are as wide or as high as the drawing area of the page.
+    GutterTooWide(Length, Grid),
 }
 impl From<std::io::Error> for ConfigError {
     fn from(value: std::io::Error) -> Self {
@@ -64,6 +131,12 @@ impl Display for ConfigError {
             ConfigError::NoValidFiles => write_t!(f, "error.no_valid_files"),
             ConfigError::MalformedPath(path) => write_t!(f, "error.invalid_config_path",path=path),
             ConfigError::MissingConfigError(path) =>write_t!(f, "error.missing_config_file",path=path),
+            ConfigError::ConflictingOptions(first, second) => {
+                write_t!(f, "error.conflicting_options", first = first, second = second)
+            }
+            ConfigError::GutterTooWide(gutter, grid) => {
+                write_t!(f, "error.gutter_too_wide", gutter = gutter, grid = grid)
+            }
         }
     }
 }
@@ -1,14 +1,29 @@
 use std::path::Path;
 
+use image::ImageFormat;
 use indicatif::ProgressBar;
 use once_cell::sync::Lazy;
 use pdfuse_utils::{error_t, get_busy_indicator};
 use pdfuse_utils::{info_t, Indexed};
+use rust_i18n::t;
 use walkdir::{DirEntry, WalkDir};
 
-pub(crate) const IMAGE_EXTENSIONS: &[&str] = &[
-    "bmp", "jpeg", "jp2", "jpg", "jpx", "jxr", "pam", "pbm", "pnm", "png", "psd", "tiff",
-];
+/// Extensions of the image formats the `image` crate was built with decoders for, and of JPEG 2000.
+///
+/// Sorted, as it is searched with `binary_search`.
+pub(crate) static IMAGE_EXTENSIONS: Lazy<Vec<&str>> = Lazy::new(|| {
+    let mut extensions: Vec<&str> = ImageFormat::all()
+        .filter(|format| can_decode(*format))
+        .flat_map(|format| format.extensions_str().iter().copied())
+        .chain(JPEG2000_EXTENSIONS.iter().copied())
+        .collect();
+    extensions.sort_unstable();
+    extensions.dedup();
+    extensions
+});
+
+/// JPEG 2000 images, which are embedded without being decoded.
+pub(crate) const JPEG2000_EXTENSIONS: &[&str] = &["jp2", "jpx"];
 
 pub(crate) const DRAWING_EXTENSIONS: &[&str] = &[
     "cdr", "odg", "otg", "pub", "std", "svg", "sxd", "vdx", "vsd", "vsdm", "vsdx",
@@ -34,7 +49,7 @@ pub(crate) const PDF_EXTENSIONS: &[&str] = &["pdf"];
 use crate::source_path::SourcePath;
 
 pub(crate) static ALL_SIMPLE_SUPPORTED_EXTENSIONS: Lazy<Vec<&str>> =
-    Lazy::new(|| [IMAGE_EXTENSIONS, PDF_EXTENSIONS].concat());
+    Lazy::new(|| [IMAGE_EXTENSIONS.as_slice(), PDF_EXTENSIONS].concat());
 
 pub(crate) static ALL_LIBRE_EXTENSIONS: Lazy<Vec<&str>> = Lazy::new(|| {
     let mut s = [
@@ -56,6 +71,39 @@ pub(crate) static ALL_SUPPORTED_EXTENSIONS: Lazy<Vec<&str>> = Lazy::new(|| {
     .concat()
 });
 
+/// Checks if the `image` crate was built with a decoder for the format.
+///
+/// AVIF counts as readable once its encoder is built, but decoding it needs the `avif-native` feature,
+/// which is not enabled.
+fn can_decode(format: ImageFormat) -> bool {
+    format.reading_enabled() && format != ImageFormat::Avif
+}
+
+/// Describes the supported input formats, one line per image format, including those without a decoder.
+pub(crate) fn supported_formats() -> String {
+    let image_line = |format: ImageFormat| {
+        format!("  {format:?}: {}\n", format.extensions_str().join(", "))
+    };
+    let (readable, unreadable): (Vec<ImageFormat>, Vec<ImageFormat>) =
+        ImageFormat::all().partition(|format| can_decode(*format));
+    let mut text = format!("{}\n", t!("formats.images"));
+    text.extend(readable.into_iter().map(image_line));
+    if !unreadable.is_empty() {
+        text += &format!("{}\n", t!("formats.images_without_decoder"));
+        text.extend(unreadable.into_iter().map(image_line));
+    }
+    let mut office = ALL_LIBRE_EXTENSIONS.clone();
+    office.dedup();
+    text += &format!(
+        "{}\n  {}\n",
+        t!("formats.jpeg2000"),
+        JPEG2000_EXTENSIONS.join(", ")
+    );
+    text += &format!("{}\n  {}\n", t!("formats.pdfs"), PDF_EXTENSIONS.join(", "));
+    text += &format!("{}\n  {}\n", t!("formats.office"), office.join(", "));
+    text
+}
+
 fn is_valid_source(entry: &DirEntry, extensions: &[&str]) -> bool {
     match entry.path().extension() {
         None => false,
@@ -166,3 +214,19 @@ pub fn get_files(
     }
     files
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn image_extensions_are_sorted() {
+        assert!(IMAGE_EXTENSIONS.is_sorted());
+        for extension in ["exr", "gif", "hdr", "jp2", "qoi", "tga", "webp"] {
+            assert!(
+                IMAGE_EXTENSIONS.binary_search(&extension).is_ok(),
+                "{extension}"
+            );
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    errors::FiltersParseError,
+    placement::{is_inside, value_name},
+};
+
+/// Cleanup of photographed or scanned documents, applied to images before they are placed.
+#[derive(Debug, Clone, Copy, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageFilter {
+    /// Drop the colors, keeping the brightness.
+    Grayscale,
+    /// Stretch the brightness so that the darkest parts become black and the lightest white.
+    AutoLevels,
+    /// Turn pixels black or white by comparing them with their surroundings, which evens out shadows.
+    AdaptiveThreshold,
+    /// Remove specks of single pixels.
+    Despeckle,
+    /// Sharpen the edges of text and lines.
+    Sharpen,
+}
+
+/// Filters of images found under a given path, written as `PATH=FILTER[,FILTER...]`.
+///
+/// `PATH=` without filters turns filtering off.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct InputFilters {
+    pub path: PathBuf,
+    pub filters: Vec<ImageFilter>,
+}
+
+impl InputFilters {
+    pub fn try_from_string(text: &str) -> Result<Self, FiltersParseError> {
+        let error = || FiltersParseError::new(text);
+        // the path itself may contain '=' or ',', the filters cannot
+        let (path, filters) = text.rsplit_once('=').ok_or_else(error)?;
+        if path.is_empty() {
+            return Err(error());
+        }
+        let filters = filters
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|x| ImageFilter::from_str(x, true).map_err(|_| error()))
+            .collect::<Result<_, _>>()?;
+        Ok(InputFilters {
+            path: PathBuf::from(path),
+            filters,
+        })
+    }
+
+    /// Checks if the filters apply to the file at `path`, which is the path itself or is inside it.
+    pub fn matches(&self, path: &Path) -> bool {
+        is_inside(path, &self.path)
+    }
+}
+
+impl TryFrom<String> for InputFilters {
+    type Error = FiltersParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from_string(&value)
+    }
+}
+
+impl From<InputFilters> for String {
+    fn from(value: InputFilters) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for InputFilters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let filters: Vec<String> = self.filters.iter().map(value_name).collect();
+        write!(f, "{}={}", self.path.display(), filters.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filters() {
+        let parsed = InputFilters::try_from_string("a=b/receipts=grayscale, auto-levels").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("a=b/receipts"));
+        assert_eq!(
+            parsed.filters,
+            [ImageFilter::Grayscale, ImageFilter::AutoLevels]
+        );
+        assert_eq!(parsed.to_string(), "a=b/receipts=grayscale,auto-levels");
+        let off = InputFilters::try_from_string("photos=").unwrap();
+        assert!(off.filters.is_empty());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for text in ["scans", "=sharpen", "scans=blur", "scans=sharpen,,x"] {
+            assert!(InputFilters::try_from_string(text).is_err(), "{text}");
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::GridParseError;
+
+/// Number of columns and rows of images on a page, written as `COLUMNSxROWS`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct Grid {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+impl Grid {
+    pub fn try_from_string(text: &str) -> Result<Self, GridParseError> {
+        let error = || GridParseError::new(text);
+        let (columns, rows) = text
+            .split_once(['x', 'X', '×'])
+            .ok_or_else(error)?;
+        let parse = |count: &str| count.trim().parse::<u32>().ok().filter(|x| *x > 0);
+        let grid = Grid {
+            columns: parse(columns).ok_or_else(error)?,
+            rows: parse(rows).ok_or_else(error)?,
+        };
+        // cells are counted in u32 while a page is laid out
+        grid.columns.checked_mul(grid.rows).ok_or_else(error)?;
+        Ok(grid)
+    }
+
+    /// Number of images on a full page.
+    pub fn cells(&self) -> u64 {
+        self.columns as u64 * self.rows as u64
+    }
+}
+
+impl TryFrom<String> for Grid {
+    type Error = GridParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from_string(&value)
+    }
+}
+
+impl From<Grid> for String {
+    fn from(value: Grid) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}", self.columns, self.rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_grid() {
+        let grid = Grid::try_from_string("2x3").unwrap();
+        assert_eq!((grid.columns, grid.rows, grid.cells()), (2, 3, 6));
+        assert_eq!(grid.to_string(), "2x3");
+        assert_eq!(Grid::try_from_string(" 4 × 5 ").unwrap().cells(), 20);
+        for text in ["2", "0x3", "2x", "axb", "2x3x4", "70000x70000"] {
+            assert!(Grid::try_from_string(text).is_err(), "{text}");
+        }
+    }
+}
@@ -1,12 +1,21 @@
 mod commandline_arguments;
 pub mod errors;
 pub mod file_finder;
+mod filters;
+mod grid;
 pub mod invalid_source_type;
 pub mod source_path;
 mod parameters;
 mod paths;
-pub use commandline_arguments::Args;
-pub use parameters::{Parameters, ParametersWithPaths};
+mod placement;
+pub use commandline_arguments::{
+    Args, AutoOrient, CaptionPosition, CollateOrder, ColorProfiles, ErrorPolicy, ImagePageSizing,
+    PdfVersion,
+};
+pub use filters::{ImageFilter, InputFilters};
+pub use grid::Grid;
+pub use placement::{Alignment, InputPlacement, PlacementMode};
+pub use parameters::{Invocation, Parameters, ParametersWithPaths};
 pub use source_path::SourcePath;
 
 rust_i18n::i18n!();
@@ -1,9 +1,13 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use pdfuse_sizing::{CustomSize, PageSize};
+use pdfuse_sizing::{CustomSize, Length, Margins, PageSize};
 use pdfuse_utils::Indexed;
 
-use crate::{errors::ConfigError, Args, SourcePath};
+use crate::{
+    errors::ConfigError, file_finder, Alignment, Args, AutoOrient, CaptionPosition, CollateOrder, ColorProfiles,
+    ErrorPolicy, Grid, ImageFilter, ImagePageSizing, InputFilters, InputPlacement, PdfVersion,
+    PlacementMode, SourcePath,
+};
 
 /// Parameters used during conversion, creation, and merging of PDFs.
 #[derive(Debug,Clone,Default)]
@@ -12,14 +16,81 @@ pub struct Parameters{
     pub what_if: bool,
     pub recursion_limit: usize,
     pub image_page_fallback_size: PageSize,
+    pub image_page_sizing: ImagePageSizing,
+    /// Page size limits when pages match images.
+    pub image_page_min_size: CustomSize,
+    pub image_page_max_size: CustomSize,
     pub image_dpi: u16,
+    /// Whether images are sized by the resolution saved in them, falling back to `image_dpi`.
+    pub use_image_dpi: bool,
+    pub color_profiles: ColorProfiles,
     pub image_quality:u8,
     pub image_lossless_compression:bool,
-    pub margin: CustomSize,
+    /// Whether images are turned black and white and compressed with CCITT Group 4.
+    pub bilevel_compression: bool,
+    /// Gray level below which pixels turn black, chosen for each image if `None`.
+    pub bilevel_threshold: Option<u8>,
+    pub ignore_exif_orientation: bool,
+    pub first_frame_only: bool,
+    pub deskew: bool,
+    /// Largest difference from the border color cut off around images, if they are cropped.
+    pub auto_crop_tolerance: Option<u8>,
+    pub filters: Vec<ImageFilter>,
+    /// Overrides of filters for inputs under the given paths.
+    pub filters_for: Vec<InputFilters>,
+    pub auto_orient: Option<AutoOrient>,
+    pub placement: PlacementMode,
+    pub alignment: Alignment,
+    /// Overrides of placement for inputs under the given paths.
+    pub placement_for: Vec<InputPlacement>,
+    pub margin: Margins,
+    /// Whether left and right margins of image pages swap on even pages of the output.
+    pub mirror_margins: bool,
+    /// Template of the caption written next to each image, without captions if `None`.
+    pub caption: Option<String>,
+    pub caption_position: CaptionPosition,
+    /// Font size of captions, in points.
+    pub caption_font_size: f32,
+    /// Font embedded for captions, Helvetica is used if `None`.
+    pub caption_font: Option<PathBuf>,
+    /// Cells of the pages that images are laid out in, one image per page if `None`.
+    pub grid: Option<Grid>,
+    /// Space between the cells of the grid.
+    pub grid_gutter: Length,
     pub force_image_page_fallback_size: bool,
     pub alphabetic_file_sorting: bool,
     pub libreoffice_path: Option<PathBuf>,
+    pub on_error: ErrorPolicy,
+    pub remove_blank_pages: bool,
+    /// Ink coverage percentage below which an image is blank.
+    pub blank_threshold: f32,
+    pub collate: Option<CollateOrder>,
+    /// Number of inputs holding the fronts when collating.
+    pub collate_fronts: Option<usize>,
+    pub separator_pages: bool,
+    pub separator_thumbnails: bool,
+    pub pdf_version: PdfVersion,
+    pub object_streams: bool,
     pub output_file: String,
+    pub report_file: Option<String>,
+}
+
+impl Parameters {
+    /// Returns the filters of the image at `path`, taking the last matching override into account.
+    pub fn filters_for(&self, path: &Path) -> &[ImageFilter] {
+        match self.filters_for.iter().rev().find(|x| x.matches(path)) {
+            Some(x) => &x.filters,
+            None => &self.filters,
+        }
+    }
+
+    /// Returns the placement of the image at `path`, taking the last matching override into account.
+    pub fn placement_for(&self, path: &Path) -> (PlacementMode, Alignment) {
+        match self.placement_for.iter().rev().find(|x| x.matches(path)) {
+            Some(x) => (x.mode, x.alignment.unwrap_or(self.alignment)),
+            None => (self.placement, self.alignment),
+        }
+    }
 }
 
 /// Parameters for operation of the main app, with paths to process.
@@ -30,9 +101,20 @@ pub struct ParametersWithPaths{
 }
 unsafe impl Send for ParametersWithPaths{}
 
+/// What the app is asked to do by its parameters.
+#[derive(Debug)]
+pub enum Invocation {
+    Merge(Box<ParametersWithPaths>),
+    /// Print the supported input formats, described by the text, and do nothing else.
+    ListFormats(String),
+}
+
 impl ParametersWithPaths{
-    pub fn parse()->Result<Self,ConfigError>{
+    pub fn parse() -> Result<Invocation, ConfigError> {
         let a = Args::create()?;
-        a.make_parameters()
+        if a.list_formats {
+            return Ok(Invocation::ListFormats(file_finder::supported_formats()));
+        }
+        a.make_parameters().map(|p| Invocation::Merge(Box::new(p)))
     }
 }
\ No newline at end of file
@@ -11,7 +11,7 @@ fn replace_env_var(caps: &Captures) -> String {
 fn expand_vars(path: &str) -> Option<String> {
     #[cfg(unix)]
     {
-        return std::fs::canonicalize(path).map(|buf| buf.to_string_lossy().into_owned())
+        return std::path::absolute(path).map(|buf| buf.to_string_lossy().into_owned())
         .ok();
     }
     static PERF_FIND: Lazy<Regex> = Lazy::new(|| Regex::new(r"(%(?<name>\w+)%)").unwrap());
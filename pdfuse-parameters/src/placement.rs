@@ -0,0 +1,165 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::PlacementParseError;
+
+/// How an image is placed in the drawing area of its page.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlacementMode {
+    /// Scale the image uniformly to fit inside the area.
+    #[default]
+    Fit,
+    /// Scale the image uniformly to cover the whole area, cropping what does not fit.
+    Fill,
+    /// Scale the image to the size of the area, ignoring its aspect ratio.
+    Stretch,
+    /// Keep the size of the image at its DPI, even if it does not fit.
+    ActualSize,
+    /// Repeat the image at its actual size to cover the area.
+    Tile,
+}
+
+/// Where an image is placed in the drawing area, when it does not cover the area exactly.
+#[derive(Debug, Clone, Copy, Default, ValueEnum, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Alignment {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    #[default]
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Alignment {
+    /// Position as fractions of the free space: from the left edge and from the bottom edge.
+    pub fn factors(&self) -> (f64, f64) {
+        let horizontal = match self {
+            Alignment::TopLeft | Alignment::Left | Alignment::BottomLeft => 0.0,
+            Alignment::Top | Alignment::Center | Alignment::Bottom => 0.5,
+            Alignment::TopRight | Alignment::Right | Alignment::BottomRight => 1.0,
+        };
+        let vertical = match self {
+            Alignment::BottomLeft | Alignment::Bottom | Alignment::BottomRight => 0.0,
+            Alignment::Left | Alignment::Center | Alignment::Right => 0.5,
+            Alignment::TopLeft | Alignment::Top | Alignment::TopRight => 1.0,
+        };
+        (horizontal, vertical)
+    }
+}
+
+/// Placement of images found under a given path, written as `PATH=MODE[,ALIGNMENT]`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct InputPlacement {
+    pub path: PathBuf,
+    pub mode: PlacementMode,
+    pub alignment: Option<Alignment>,
+}
+
+impl InputPlacement {
+    pub fn try_from_string(text: &str) -> Result<Self, PlacementParseError> {
+        let error = || PlacementParseError::new(text);
+        // the path itself may contain '=' or ',', the placement cannot
+        let (path, placement) = text.rsplit_once('=').ok_or_else(error)?;
+        let (mode, alignment) = match placement.split_once(',') {
+            Some((mode, alignment)) => (mode, Some(alignment)),
+            None => (placement, None),
+        };
+        let mode = PlacementMode::from_str(mode.trim(), true).map_err(|_| error())?;
+        let alignment = alignment
+            .map(|x| Alignment::from_str(x.trim(), true))
+            .transpose()
+            .map_err(|_| error())?;
+        if path.is_empty() {
+            return Err(error());
+        }
+        Ok(InputPlacement {
+            path: PathBuf::from(path),
+            mode,
+            alignment,
+        })
+    }
+
+    /// Checks if the placement applies to the file at `path`, which is the path itself or is inside it.
+    pub fn matches(&self, path: &Path) -> bool {
+        is_inside(path, &self.path)
+    }
+}
+
+impl TryFrom<String> for InputPlacement {
+    type Error = PlacementParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from_string(&value)
+    }
+}
+
+impl From<InputPlacement> for String {
+    fn from(value: InputPlacement) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for InputPlacement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}={}", self.path.display(), value_name(&self.mode))?;
+        if let Some(alignment) = &self.alignment {
+            write!(f, ",{}", value_name(alignment))?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks if `path` is `base` itself or is inside it, also after resolving links.
+pub(crate) fn is_inside(path: &Path, base: &Path) -> bool {
+    path.starts_with(base) || fs::canonicalize(path).is_ok_and(|x| x.starts_with(base))
+}
+
+pub(crate) fn value_name(value: &impl ValueEnum) -> String {
+    value
+        .to_possible_value()
+        .map(|x| x.get_name().to_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_mode_only() {
+        let parsed = InputPlacement::try_from_string("scans=fill").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("scans"));
+        assert_eq!(parsed.mode, PlacementMode::Fill);
+        assert_eq!(parsed.alignment, None);
+    }
+
+    #[test]
+    fn parse_mode_and_alignment() {
+        let parsed = InputPlacement::try_from_string("a=b/photos=actual-size, top-left").unwrap();
+        assert_eq!(parsed.path, PathBuf::from("a=b/photos"));
+        assert_eq!(parsed.mode, PlacementMode::ActualSize);
+        assert_eq!(parsed.alignment, Some(Alignment::TopLeft));
+        assert_eq!(parsed.to_string(), "a=b/photos=actual-size,top-left");
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for text in ["scans", "=fit", "scans=sideways", "scans=fit,middle"] {
+            assert!(InputPlacement::try_from_string(text).is_err(), "{text}");
+        }
+    }
+}
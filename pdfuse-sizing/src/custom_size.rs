@@ -13,7 +13,7 @@ use super::{
     iso_paper::IsoPaper, page_size::PageSize, size::Size, unit::Unit, length::Length,
 };
 
-#[derive(Debug, PartialEq, Clone, Copy,Serialize,Deserialize)]
+#[derive(Debug, Default, PartialEq, Clone, Copy,Serialize,Deserialize)]
 #[serde(try_from ="String")]
 #[serde(into ="String")]
 pub struct CustomSize {
@@ -35,12 +35,6 @@ where
     }
 }
 
-impl Default for CustomSize{
-    fn default() -> Self {
-        Self { horizontal: Length::zero(), vertical: Length::zero() }
-    }
-}
-
 impl TryFrom<&str> for CustomSize {
     type Error = LengthParseError;
 
@@ -210,6 +204,14 @@ impl CustomSize {
             vertical: Length::from_points(vertical),
         }
     }
+    /// Scales the size uniformly, so that it is not smaller than `min` nor larger than `max`.
+    ///
+    /// If both cannot be met (very long or very tall sizes), staying within `max` wins.
+    pub fn clamped(&self, min: &CustomSize, max: &CustomSize) -> CustomSize {
+        let grow = (min.horizontal / self.horizontal).max(min.vertical / self.vertical);
+        let scale = grow.max(1.0).min(max.fit_size(self));
+        *self * scale
+    }
     pub fn try_from_string(text: &str) -> Result<Self, LengthParseError> {
         static LAST_UNIT_REGEX: Lazy<Regex> =
             Lazy::new(|| Regex::new(r"(?i)([A-Z]+)[\s;,]*$").unwrap());
@@ -309,4 +311,22 @@ mod tests {
             assert_eq!(parsed, paper, "{}", text);
         }
     }
+
+    #[test]
+    fn clamping() {
+        let min = CustomSize::from_inches(2.0, 2.0);
+        let max = CustomSize::from_inches(10.0, 10.0);
+        let test_vals = vec![
+            (CustomSize::from_inches(4.0, 5.0), CustomSize::from_inches(4.0, 5.0)),
+            (CustomSize::from_inches(1.0, 0.5), CustomSize::from_inches(4.0, 2.0)),
+            (CustomSize::from_inches(40.0, 20.0), CustomSize::from_inches(10.0, 5.0)),
+            (CustomSize::from_inches(1.0, 50.0), CustomSize::from_inches(0.2, 10.0)),
+        ];
+        for (size, expected) in test_vals {
+            let clamped = size.clamped(&min, &max);
+            let error = (clamped - expected).horizontal.inch().abs()
+                + (clamped - expected).vertical.inch().abs();
+            assert!(error < 1e-9, "{size} -> {clamped}, expected {expected}");
+        }
+    }
 }
@@ -28,7 +28,8 @@ impl Error for PageSizeError {}
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord,Debug)]
 pub enum LengthParseError{
     InvalidUnit(UnitParseError),
-    NoValueSpecified
+    NoValueSpecified,
+    TooManyValues,
 }
 impl Error for LengthParseError{}
 
@@ -42,6 +43,7 @@ impl Display for LengthParseError{
         match self{
             LengthParseError::InvalidUnit(unit_parse_error) => unit_parse_error.fmt(f),
             LengthParseError::NoValueSpecified => write!(f,"Value is not specified"),
+            LengthParseError::TooManyValues => write!(f,"Too many values are specified"),
         }
     }
 }
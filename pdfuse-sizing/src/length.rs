@@ -17,6 +17,11 @@ use super::{parsing::ParseResult, unit::Unit};
 pub struct Length {
     pub(crate) base_value: f64,
 }
+impl Default for Length {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
 impl From<Length> for String {
     fn from(value: Length) -> Self {
         value.as_unit_str(Length::BASE_UNIT)
@@ -6,6 +6,7 @@ pub(crate) mod parsing;
 mod size;
 mod unit;
 mod length;
+mod margins;
 
 pub use custom_size::CustomSize;
 pub use errors::*;
@@ -14,6 +15,7 @@ pub use page_size::PageSize;
 pub use size::Size;
 pub use unit::Unit;
 pub use length::Length;
+pub use margins::Margins;
 
 #[cfg(test)]
 mod tests {
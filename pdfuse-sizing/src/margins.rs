@@ -0,0 +1,230 @@
+use std::fmt::Display;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::errors::LengthParseError;
+
+use super::{custom_size::CustomSize, length::Length, unit::Unit};
+
+/// Space left free on each side of a page.
+///
+/// Written like CSS margins, with 1 to 4 lengths:
+/// all sides; top and bottom, left and right; top, left and right, bottom; or top, right, bottom, left.
+/// `WIDTH x HEIGHT` gives the total horizontal and vertical margin, split evenly between the sides.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+#[serde(try_from = "String")]
+#[serde(into = "String")]
+pub struct Margins {
+    pub top: Length,
+    pub right: Length,
+    pub bottom: Length,
+    pub left: Length,
+}
+
+impl Default for Margins {
+    fn default() -> Self {
+        Self::zero()
+    }
+}
+
+impl From<CustomSize> for Margins {
+    /// Splits the total horizontal and vertical margin evenly between the sides.
+    fn from(value: CustomSize) -> Self {
+        let (horizontal, vertical) = (value.horizontal / 2.0, value.vertical / 2.0);
+        Margins {
+            top: vertical,
+            right: horizontal,
+            bottom: vertical,
+            left: horizontal,
+        }
+    }
+}
+
+impl TryFrom<&str> for Margins {
+    type Error = LengthParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::try_from_string(value)
+    }
+}
+
+impl TryFrom<String> for Margins {
+    type Error = LengthParseError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::try_from_string(&value)
+    }
+}
+
+impl From<Margins> for String {
+    fn from(value: Margins) -> Self {
+        value.to_string()
+    }
+}
+
+impl Display for Margins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = format!(
+            "{top} {right} {bottom} {left}",
+            top = self.top,
+            right = self.right,
+            bottom = self.bottom,
+            left = self.left
+        );
+        f.pad(&msg)
+    }
+}
+
+impl Margins {
+    pub fn zero() -> Self {
+        Margins {
+            top: Length::zero(),
+            right: Length::zero(),
+            bottom: Length::zero(),
+            left: Length::zero(),
+        }
+    }
+    /// Sum of the left and right margin, and of the top and bottom margin.
+    pub fn total(&self) -> CustomSize {
+        CustomSize {
+            horizontal: self.left + self.right,
+            vertical: self.top + self.bottom,
+        }
+    }
+    /// Distances of the area inside the margins from the left and bottom edges, where PDF pages have their origin.
+    pub fn bottom_left(&self) -> CustomSize {
+        CustomSize {
+            horizontal: self.left,
+            vertical: self.bottom,
+        }
+    }
+    /// Distances of the area inside the margins from the right and top edges.
+    pub fn top_right(&self) -> CustomSize {
+        CustomSize {
+            horizontal: self.right,
+            vertical: self.top,
+        }
+    }
+    /// The margins with left and right swapped, as on the other side of a sheet printed on both sides.
+    pub fn mirrored(&self) -> Margins {
+        Margins {
+            left: self.right,
+            right: self.left,
+            ..*self
+        }
+    }
+    pub fn try_from_string(text: &str) -> Result<Self, LengthParseError> {
+        if SIZE_REGEX.is_match(text) {
+            return CustomSize::try_from_string(text).map(Margins::from);
+        }
+        let [top, right, bottom, left] = match lengths(text)?[..] {
+            [all] => [all, all, all, all],
+            [vertical, horizontal] => [vertical, horizontal, vertical, horizontal],
+            [top, horizontal, bottom] => [top, horizontal, bottom, horizontal],
+            [top, right, bottom, left] => [top, right, bottom, left],
+            [] => return Err(LengthParseError::NoValueSpecified),
+            _ => return Err(LengthParseError::TooManyValues),
+        };
+        Ok(Margins {
+            top,
+            right,
+            bottom,
+            left,
+        })
+    }
+    /// Whether the text gives one or two lengths other than zero, which meant the total horizontal and vertical
+    /// margin before the sides could be set one by one. `WIDTH x HEIGHT` still has that meaning.
+    pub fn meaning_changed(text: &str) -> bool {
+        !SIZE_REGEX.is_match(text)
+            && lengths(text).is_ok_and(|lengths| {
+                (1..=2).contains(&lengths.len()) && lengths.iter().any(|x| x.pt() != 0.0)
+            })
+    }
+}
+
+/// Matches `WIDTH x HEIGHT`, the total horizontal and vertical margin.
+static SIZE_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)[\d.]\s*[A-Z]*\s*[x\-]\s*[\d.]").unwrap());
+
+/// The lengths written one after another, those without a unit taking the last unit given.
+fn lengths(text: &str) -> Result<Vec<Length>, LengthParseError> {
+    static LAST_UNIT_REGEX: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"(?i)([A-Z]+)[\s;,]*$").unwrap());
+    let last_unit = match LAST_UNIT_REGEX.captures(text).and_then(|c| c.get(1)) {
+        None => None,
+        Some(x) => Some(Unit::from_string(x.as_str())?),
+    };
+    let mut lengths = vec![];
+    let mut start = 0;
+    while let Ok(result) = Length::from_string_with_default(&text[start..], last_unit) {
+        lengths.push(result.parsed_value);
+        start += result.end_position;
+    }
+    Ok(lengths)
+}
+
+#[cfg(test)]
+mod tests {
+    #![allow(clippy::expect_fun_call)]
+
+    use super::*;
+
+    fn margins(top: f64, right: f64, bottom: f64, left: f64) -> Margins {
+        Margins {
+            top: Length::from_millimeters(top),
+            right: Length::from_millimeters(right),
+            bottom: Length::from_millimeters(bottom),
+            left: Length::from_millimeters(left),
+        }
+    }
+
+    #[test]
+    fn parsing_margins() {
+        let test_vals = vec![
+            ("10mm", margins(10.0, 10.0, 10.0, 10.0)),
+            ("10mm 20mm", margins(10.0, 20.0, 10.0, 20.0)),
+            ("10 20 mm", margins(10.0, 20.0, 10.0, 20.0)),
+            ("10mm, 2cm, 30mm", margins(10.0, 20.0, 30.0, 20.0)),
+            ("1 2 3 4 cm", margins(10.0, 20.0, 30.0, 40.0)),
+            ("5 mm x 0 mm", margins(0.0, 2.5, 0.0, 2.5)),
+            ("0", Margins::zero()),
+        ];
+        for (text, expected) in test_vals {
+            let parsed =
+                Margins::try_from_string(text).expect(&format!("Failed parsing '{}'", text));
+            assert_eq!(parsed, expected, "{}", text);
+        }
+        let inches = Margins::try_from_string("1in 0.5in 1in 0.5in").unwrap();
+        assert_eq!(inches.left, Length::from_inches(0.5));
+        assert_eq!(
+            Margins::try_from_string(&inches.to_string()).unwrap(),
+            inches
+        );
+    }
+
+    #[test]
+    fn parsing_invalid_margins() {
+        for text in ["", "mm", "1 2 3 4 5 mm", "10 parsecs"] {
+            assert!(Margins::try_from_string(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn telling_changed_meaning() {
+        for text in ["10mm", "10 20 mm"] {
+            assert!(Margins::meaning_changed(text), "{}", text);
+        }
+        for text in ["0", "0 0 mm", "5 mm x 0 mm", "1 2 3 cm", "1 2 3 4 cm", "mm"] {
+            assert!(!Margins::meaning_changed(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn mirroring() {
+        let binding = margins(10.0, 10.0, 10.0, 20.0);
+        assert_eq!(binding.mirrored(), margins(10.0, 20.0, 10.0, 10.0));
+        assert_eq!(binding.total(), CustomSize::from_millimeters(30.0, 20.0));
+    }
+}
@@ -55,6 +55,21 @@ macro_rules! trace_t {
     }};
 }
 
+/// Logs translated text (with optional arguments) as warning
+#[macro_export]
+macro_rules! warn_t {
+
+    ($key:expr) => {{
+        let translated_message = $crate::rust_i18n::t!($key);
+        $crate::log::warn!("{}", translated_message);
+    }};
+
+    ($key:expr, $($t_args:tt)+) => {{
+        let translated_message = $crate::rust_i18n::t!($key, $($t_args)*);
+        $crate::log::warn!("{}", translated_message);
+    }};
+}
+
 /// Logs translated text (with optional arguments) as error
 #[macro_export]
 macro_rules! error_t {
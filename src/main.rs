@@ -1,6 +1,15 @@
+use pdfuse_merging::MergeOutcome;
+use pdfuse_parameters::Invocation;
 use pdfuse_utils::info_t;
 rust_i18n::i18n!();
 
+/// Exit code when the parameters or the configuration file are invalid.
+const EXIT_CONFIG_ERROR: i32 = 1;
+/// Exit code when the output was written, but some inputs are missing from it.
+const EXIT_PARTIAL_MERGE: i32 = 2;
+/// Exit code when nothing could be merged.
+const EXIT_FAILURE: i32 = 3;
+
 fn main() {
     log::set_logger(&pdfuse_utils::CONSOLE_LOGGER).expect("Setting logger cannot fail!");
     #[cfg(debug_assertions)]
@@ -9,13 +18,22 @@ fn main() {
     log::set_max_level(log::LevelFilter::Trace);
     let start_time = std::time::Instant::now();
     let parameters = match pdfuse_parameters::ParametersWithPaths::parse() {
-        Ok(p) => p,
+        Ok(Invocation::Merge(p)) => *p,
+        Ok(Invocation::ListFormats(formats)) => {
+            print!("{formats}");
+            return;
+        }
         Err(e) => {
             print!("{e}");
-            std::process::exit(1);
+            std::process::exit(EXIT_CONFIG_ERROR);
         }
     };
-    pdfuse_merging::load(parameters.files.to_owned(), &parameters.parameters);
+    let outcome = pdfuse_merging::load(parameters.files.to_owned(), &parameters.parameters);
     let end_time = std::time::Instant::now();
     info_t!("time_taken",duration_seconds=(end_time-start_time).as_secs_f32()); 
+    match outcome {
+        MergeOutcome::Complete => (),
+        MergeOutcome::Partial { .. } => std::process::exit(EXIT_PARTIAL_MERGE),
+        MergeOutcome::Failed => std::process::exit(EXIT_FAILURE),
+    }
 }